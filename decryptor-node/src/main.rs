@@ -1,9 +1,10 @@
 use std::env::{self, VarError};
-use babyjubjub_elgamal::{Node, KeygenHelper};
-use babyjubjub_rs::Point;
-use rocket::{State, serde::json::Json, response::status::BadRequest};
+use std::time::{SystemTime, UNIX_EPOCH};
+use babyjubjub_elgamal::{Node, KeygenHelper, KeygenCommitment, Error, keygen_helpers_from_json, tdh2::{LabeledCiphertext, CiphertextLabel}, encoding::CompactEncoding};
+use rocket::{State, response::status::Custom};
 use rocket::{Request, Response, fairing::{Fairing, Info, Kind}, http::{Header, Status}};
 use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
 
 #[macro_use] extern crate rocket;
 
@@ -14,9 +15,41 @@ const TOTAL_NODES: usize = 2;
 pub struct Cors;
 #[derive(Serialize,Deserialize)]
 pub struct DecryptionRequest {
-    pub c1: Point,
+    pub ciphertext: LabeledCiphertext,
     pub nodes_to_decrypt_from: Vec<u32>,
 }
+#[derive(Serialize,Deserialize)]
+pub struct RawDecryptionRequest {
+    pub ciphertext: LabeledCiphertext,
+}
+/// The body of a request with `?encoding=compact`: the same as a `DecryptionRequest`, but the ciphertext is the hex of its compact encoding.
+/// `nodes_to_decrypt_from` is left out for `/raw-decrypt`
+#[derive(Serialize,Deserialize)]
+pub struct CompactDecryptionRequest {
+    pub ciphertext: String,
+    #[serde(default)]
+    pub nodes_to_decrypt_from: Vec<u32>,
+}
+/// Which ciphertexts this node is willing to decrypt
+pub struct DecryptionPolicy {
+    /// Purposes a ciphertext's label may have. Any purpose is allowed if this is None
+    pub allowed_purposes: Option<Vec<String>>,
+}
+
+impl DecryptionPolicy {
+    fn allows(&self, label: &CiphertextLabel) -> Result<(), String> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        if label.expired(now) {
+            return Err(format!("ciphertext for user {} expired at {}", label.user_id, label.expiry));
+        }
+        if let Some(allowed) = &self.allowed_purposes {
+            if !allowed.contains(&label.purpose) {
+                return Err(format!("this node does not decrypt ciphertexts for purpose \"{}\"", label.purpose));
+            }
+        }
+        Ok(())
+    }
+}
 
 #[rocket::async_trait]
 impl Fairing for Cors {
@@ -44,29 +77,94 @@ impl Fairing for Cors {
 #[get("/")]
 fn do_nothing() -> &'static str { "GM" }
 
-#[post("/decrypt", format = "json", data = "<decrypt_request>")]
-fn index(node: &State<Node>, decrypt_request: Json<DecryptionRequest>) -> Result<String, BadRequest<&'static str>> {
-    // Check it is safe to proceed, i.e. point is on the curve and in subgroup
-    if !decrypt_request.c1.on_curve() {
-        return Err(BadRequest(Some("Not on curve")));
+/// Maps library errors to HTTP errors: problems with the request are 400s, anything else means this node is misconfigured
+fn error_response(e: Error) -> Custom<String> {
+    match e {
+        Error::MissingKeyshare | Error::ProofFailed(_) => Custom(Status::InternalServerError, e.to_string()),
+        _ => Custom(Status::BadRequest, e.to_string()),
+    }
+}
+
+/// Checks the ciphertext is bound to its label and the label is allowed by this node's policy. Every route calls this before decrypting anything
+fn check_ciphertext(policy: &DecryptionPolicy, ciphertext: &LabeledCiphertext) -> Result<(), Custom<String>> {
+    if !ciphertext.verify() {
+        return Err(error_response(Error::InvalidCiphertext));
     }
+    policy.allows(&ciphertext.label).map_err(|e| Custom(Status::Forbidden, e))?;
+    println!("decrypting for user {}, purpose \"{}\"", ciphertext.label.user_id, ciphertext.label.purpose);
+    Ok(())
+}
+
+fn unknown_encoding(encoding: &str) -> Custom<String> {
+    Custom(Status::BadRequest, format!("unknown encoding \"{}\"", encoding))
+}
+
+/// Parses a request body as a `DecryptionRequest`, or as a `CompactDecryptionRequest` if the request has `?encoding=compact`
+fn decode_request(body: &str, encoding: Option<&str>) -> Result<DecryptionRequest, Custom<String>> {
+    let bad_request = |e: serde_json::Error| Custom(Status::BadRequest, e.to_string());
+    match encoding {
+        None | Some("json") => serde_json::from_str(body).map_err(bad_request),
+        Some("compact") => {
+            let request: CompactDecryptionRequest = serde_json::from_str(body).map_err(bad_request)?;
+            let bytes = hex::decode(&request.ciphertext).map_err(|e| Custom(Status::BadRequest, format!("ciphertext is not hex: {}", e)))?;
+            Ok(DecryptionRequest {
+                ciphertext: LabeledCiphertext::from_compact(&bytes).map_err(error_response)?,
+                nodes_to_decrypt_from: request.nodes_to_decrypt_from
+            })
+        },
+        Some(other) => Err(unknown_encoding(other)),
+    }
+}
 
-    // Note: in_subgroup just checks that order of the point is the order of the subgroup
-    if !decrypt_request.c1.in_subgroup() {
-        return Err(BadRequest(Some("Not in subgroup")));
+/// Serializes a decryption share as JSON, or as the hex of its compact encoding if the request has `?encoding=compact`
+fn encode_response<T: Serialize + CompactEncoding>(result: &T, encoding: Option<&str>) -> Result<String, Custom<String>> {
+    match encoding {
+        None | Some("json") => Ok(serde_json::to_string(result).unwrap()),
+        Some("compact") => Ok(hex::encode(result.to_compact())),
+        Some(other) => Err(unknown_encoding(other)),
     }
+}
 
-    let result = node.partial_decrypt(&decrypt_request.c1, &decrypt_request.nodes_to_decrypt_from); 
-    Ok(serde_json::to_string(&result).unwrap())
+#[post("/decrypt?<encoding>", format = "json", data = "<body>")]
+fn index(node: &State<Node>, policy: &State<DecryptionPolicy>, body: &str, encoding: Option<&str>) -> Result<String, Custom<String>> {
+    let decrypt_request = decode_request(body, encoding)?;
+    check_ciphertext(policy, &decrypt_request.ciphertext)?;
+    // try_partial_decrypt checks it is safe to proceed, i.e. the ciphertext's proof verifies and C1 is on the curve and in subgroup
+    let result = node.try_partial_decrypt(&decrypt_request.ciphertext.ciphertext, &decrypt_request.nodes_to_decrypt_from)
+        .map_err(error_response)?;
+    encode_response(&result, encoding)
     // format!("Hello, world! my private key is {}. you want me to multiply it by {:?}", privkey, point)
 }
 
+/// Same as `/decrypt`, but the response includes a proof that the decryption share was computed with this node's keyshare
+#[post("/verifiable-decrypt?<encoding>", format = "json", data = "<body>")]
+fn verifiable_decrypt(node: &State<Node>, policy: &State<DecryptionPolicy>, body: &str, encoding: Option<&str>) -> Result<String, Custom<String>> {
+    let decrypt_request = decode_request(body, encoding)?;
+    check_ciphertext(policy, &decrypt_request.ciphertext)?;
+    let result = node.try_partial_decrypt_with_proof(&decrypt_request.ciphertext.ciphertext, &decrypt_request.nodes_to_decrypt_from)
+        .map_err(error_response)?;
+    encode_response(&result, encoding)
+}
+
+/// Returns this node's decryption share without its Lagrange basis applied, so the client can combine it with shares from whichever nodes respond
+#[post("/raw-decrypt?<encoding>", format = "json", data = "<body>")]
+fn raw_decrypt(node: &State<Node>, policy: &State<DecryptionPolicy>, body: &str, encoding: Option<&str>) -> Result<String, Custom<String>> {
+    let decrypt_request = match encoding {
+        None | Some("json") => serde_json::from_str::<RawDecryptionRequest>(body).map_err(|e| Custom(Status::BadRequest, e.to_string()))?,
+        _ => RawDecryptionRequest { ciphertext: decode_request(body, encoding)?.ciphertext },
+    };
+    check_ciphertext(policy, &decrypt_request.ciphertext)?;
+    let result = node.try_raw_partial_decrypt(&decrypt_request.ciphertext.ciphertext)
+        .map_err(error_response)?;
+    encode_response(&result, encoding)
+}
 
 #[launch]
 fn rocket() -> _ {
     // Get the node's private key seed key env var
-    let privkey: String = env::var("ZK_ESCROW_SECRET_SEED")
-        .expect("ZK_ESCROW_SECRET_SEED must be an environment variable. It should be a random 32-byte hex string from a secure random number generator.");
+    // The seed and everything derived from it is zeroized once the node has been made
+    let privkey = Zeroizing::new(env::var("ZK_ESCROW_SECRET_SEED")
+        .expect("ZK_ESCROW_SECRET_SEED must be an environment variable. It should be a random 32-byte hex string from a secure random number generator."));
     
     let my_node_number: usize = env::var("ZK_ESCROW_NODE_NUMBER")
         .expect("ZK_ESCROW_NODE_NUMBER must be an environment variable. It should be an integer between 1 and the total number of nodes.")
@@ -78,34 +176,49 @@ fn rocket() -> _ {
         .parse()
         .unwrap();
 
-    let mut node: Node = Node::init_from_seed(
-        &hex::decode(privkey).unwrap(), 
+    let seed = Zeroizing::new(hex::decode(&*privkey).expect("ZK_ESCROW_SECRET_SEED must be a hex string"));
+    let mut node: Node = Node::try_init_from_seed(
+        &seed, 
         my_node_number,
         THRESHOLD_NODES,
         TOTAL_NODES, 
-    );
+    ).unwrap_or_else(|e| panic!("Failed to initialize node: {}", e));
 
     let keygen_evals_for_me: Vec<String>;
     // If keygen step one has not been done, do it now
     match env::var("ZK_ESCROW_KEYGEN_EVALUATIONS_FOR_MY_NODE") {
         Ok(s) => { 
-            println!("String is {}", s);
-            let keygen_helpers: Vec<KeygenHelper> = serde_json::from_str(&s.replace("\\", "")).unwrap(); 
+            let s = Zeroizing::new(s);
+            // helpers stored before they recorded their senders are taken to be in order of sending node
+            let keygen_helpers: Vec<KeygenHelper> = keygen_helpers_from_json(&s.replace("\\", "")).unwrap(); 
             let as_pointers: Vec<&KeygenHelper> = keygen_helpers.iter().collect();
-            node.set_keyshare(&as_pointers);
+
+            let commitments: Vec<KeygenCommitment> = serde_json::from_str(
+                &env::var("ZK_ESCROW_KEYGEN_COMMITMENTS")
+                .expect("ZK_ESCROW_KEYGEN_COMMITMENTS must be an environment variable. It should be a JSON array of every node's KeygenCommitment, including this node's.")
+                .replace("\\", "")
+            ).unwrap();
+            let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
+
+            node.set_keyshare(&as_pointers, &commitments)
+                .unwrap_or_else(|e| panic!("Failed to set keyshare: {}", e));
         },
         Err(e) => {
-            let deleteme = node.keygen_step1(TOTAL_NODES);
-            let deleteme2 = serde_json::to_string(&deleteme).unwrap();
-            let deleteme3: Vec<KeygenHelper> = serde_json::from_str(&deleteme2).unwrap();
-            println!("abc {:?} !!!!!! {:?}", deleteme, deleteme3);
             let keygen = node.keygen_step1(TOTAL_NODES);
-            panic!("Keygen step 1 has not been done yet. Please perform keygen on all nodes by exchanging the shares meant for them. Then store an array of the KeygenHelpers for your node in JSON format as the env var ZK_ESCROW_KEYGEN_EVALUATIONS_FOR_MY_NODE. Then you may run this again. My KeygenHelpers for the other nodes are: {:?}", serde_json::to_string(&keygen).unwrap());
+            panic!("Keygen step 1 has not been done yet. Please perform keygen on all nodes by exchanging the shares meant for them and broadcasting every node's commitment. Then store an array of the KeygenHelpers for your node in JSON format as the env var ZK_ESCROW_KEYGEN_EVALUATIONS_FOR_MY_NODE, and an array of all nodes' KeygenCommitments as ZK_ESCROW_KEYGEN_COMMITMENTS. Then you may run this again. My KeygenHelpers for the other nodes are: {:?} My KeygenCommitment is: {:?}", serde_json::to_string(&keygen).unwrap(), serde_json::to_string(&node.keygen_commitment()).unwrap());
         }
     }
 
+    // Comma-separated list of purposes this node will decrypt for. If it isn't set, any purpose is allowed
+    let policy = DecryptionPolicy {
+        allowed_purposes: env::var("ZK_ESCROW_ALLOWED_PURPOSES").ok().map(
+            |s| s.split(',').map(|p| p.trim().to_string()).collect()
+        )
+    };
+
     rocket::build()
     .manage(node)
+    .manage(policy)
     .attach(Cors)
-    .mount("/", routes![index, do_nothing])
+    .mount("/", routes![index, verifiable_decrypt, raw_decrypt, do_nothing])
 }
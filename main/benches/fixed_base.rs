@@ -0,0 +1,52 @@
+// Compares multiplication by B8 and by a public key, and ElGamal encryption, with and without the fixed-base tables. Run with `cargo bench -p babyjubjub-elgamal`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use num_bigint::{BigInt, RandBigInt};
+use babyjubjub_rs::{Fl, B8, SUBORDER, FrBigIntConversion, encrypt_elgamal};
+use babyjubjub_elgamal::fixed_base::{self, FixedBaseTable, mul_b8, mul_b8_ct, b8_table};
+use babyjubjub_elgamal::constant_time::mul_scalar_ct;
+
+fn random_scalar() -> BigInt {
+    rand::thread_rng().gen_bigint_range(&BigInt::from(0u8), &SUBORDER)
+}
+
+fn bench_b8(c: &mut Criterion) {
+    let k = random_scalar();
+    // build the table outside the measurements
+    b8_table();
+    let mut group = c.benchmark_group("B8 multiplication");
+    group.bench_function("Point::mul_scalar", |b| b.iter(|| B8.mul_scalar(black_box(&k))));
+    group.bench_function("mul_b8", |b| b.iter(|| mul_b8(black_box(&k))));
+    let k_fl = Fl::from_bigint(&k);
+    group.bench_function("mul_scalar_ct", |b| b.iter(|| mul_scalar_ct(&B8, black_box(&k_fl))));
+    group.bench_function("mul_b8_ct", |b| b.iter(|| mul_b8_ct(black_box(&k_fl))));
+    group.finish();
+}
+
+fn bench_pubkey(c: &mut Criterion) {
+    let k = random_scalar();
+    let pubkey = B8.mul_scalar(&random_scalar());
+    let mut group = c.benchmark_group("public key multiplication");
+    group.bench_function("Point::mul_scalar", |b| b.iter(|| pubkey.mul_scalar(black_box(&k))));
+    let table = FixedBaseTable::new(&pubkey).unwrap();
+    group.bench_function("FixedBaseTable::mul_scalar", |b| b.iter(|| table.mul_scalar(black_box(&k))));
+    let k_fl = Fl::from_bigint(&k);
+    group.bench_function("FixedBaseTable::mul_scalar_ct", |b| b.iter(|| table.mul_scalar_ct(black_box(&k_fl))));
+    group.bench_function("FixedBaseTable::new", |b| b.iter(|| FixedBaseTable::new(black_box(&pubkey)).unwrap()));
+    group.finish();
+}
+
+fn bench_encrypt(c: &mut Criterion) {
+    let nonce = random_scalar();
+    let pubkey = B8.mul_scalar(&random_scalar());
+    let msg = B8.mul_scalar(&random_scalar());
+    let table = FixedBaseTable::new(&pubkey).unwrap();
+    b8_table();
+    let mut group = c.benchmark_group("ElGamal encryption");
+    group.bench_function("babyjubjub_rs::encrypt_elgamal", |b| b.iter(|| encrypt_elgamal(&pubkey, black_box(&nonce), &msg)));
+    group.bench_function("fixed_base::encrypt_elgamal", |b| b.iter(|| fixed_base::encrypt_elgamal(&pubkey, black_box(&nonce), &msg)));
+    group.bench_function("fixed_base::encrypt_elgamal_with_table", |b| b.iter(|| fixed_base::encrypt_elgamal_with_table(&table, black_box(&nonce), &msg)));
+    group.finish();
+}
+
+criterion_group!(benches, bench_b8, bench_pubkey, bench_encrypt);
+criterion_main!(benches);
@@ -0,0 +1,22 @@
+// Compares combining 50 shares with multi_scalar_mul against multiplying each with Point::mul_scalar and adding. Run with `cargo bench -p babyjubjub-elgamal`
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use num_bigint::{BigInt, RandBigInt};
+use babyjubjub_rs::{Point, B8, SUBORDER};
+use babyjubjub_elgamal::msm::multi_scalar_mul;
+
+const SHARES: usize = 50;
+
+fn bench_msm(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let points: Vec<Point> = (0..SHARES).map(|_| B8.mul_scalar(&rng.gen_bigint_range(&BigInt::from(0u8), &SUBORDER))).collect();
+    let scalars: Vec<BigInt> = (0..SHARES).map(|_| rng.gen_bigint_range(&BigInt::from(0u8), &SUBORDER)).collect();
+    let mut group = c.benchmark_group("combining 50 shares");
+    group.bench_function("Point::mul_scalar and add", |b| b.iter(|| {
+        points.iter().zip(black_box(&scalars)).map(|(p, k)| p.mul_scalar(k)).reduce(|a, b| a.add(&b)).unwrap()
+    }));
+    group.bench_function("multi_scalar_mul", |b| b.iter(|| multi_scalar_mul(&points, black_box(&scalars)).unwrap()));
+    group.finish();
+}
+
+criterion_group!(benches, bench_msm);
+criterion_main!(benches);
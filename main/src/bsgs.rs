@@ -0,0 +1,212 @@
+/*
+Baby-step giant-step tables for decoding exponential ElGamal messages
+
+Finding m in [0, max] from m*B8 takes a table of the baby steps j*B8 for 0 <= j < step, where step is about sqrt(max). Then m = i*step + j for the first i
+where p - i*step*B8 is in the table. Building the table takes as long as the search itself, so for big bounds it is worth building once and saving to disk.
+
+File format, all integers little-endian:
+    magic "BJJBSGS1" | max: u64 | step: u64 | number of entries: u64 | entries
+Each entry is 12 bytes: the first 8 bytes of the compressed baby step as a u64, then j as a u32. Entries are sorted by key so they can be binary searched
+in place, which is what lets a memory-mapped table be used without reading all of it. Keys are truncated, so a match is only a candidate and is checked
+before being returned
+*/
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::ops::Deref;
+use std::path::Path;
+use num_bigint::BigInt;
+use babyjubjub_rs::{Point, Fr, B8};
+use ff::Field;
+#[cfg(not(target_arch = "wasm32"))]
+use memmap2::Mmap;
+#[cfg(not(target_arch = "wasm32"))]
+use rayon::prelude::*;
+
+const MAGIC: &[u8; 8] = b"BJJBSGS1";
+const HEADER_LEN: usize = 32;
+const ENTRY_LEN: usize = 12;
+
+enum TableBytes {
+    Owned(Vec<u8>),
+    #[cfg(not(target_arch = "wasm32"))]
+    Mapped(Mmap),
+}
+
+impl Deref for TableBytes {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        match self {
+            TableBytes::Owned(v) => v,
+            #[cfg(not(target_arch = "wasm32"))]
+            TableBytes::Mapped(m) => m,
+        }
+    }
+}
+
+/// Lookup table for finding m in [0, max] from m*B8
+pub struct BsgsTable {
+    max: u64,
+    step: u64,
+    bytes: TableBytes,
+}
+
+impl BsgsTable {
+    /// Builds the table for messages up to `max`. It has about sqrt(max) entries of 12 bytes each
+    pub fn build(max: u64) -> BsgsTable {
+        let step = (max as f64).sqrt() as u64 + 1;
+        assert!(step <= u32::MAX as u64, "max {} is too large for a table", max);
+        let mut entries: Vec<(u64, u32)> = Vec::with_capacity(step as usize);
+        let mut acc = identity();
+        for j in 0..step {
+            entries.push((key(&acc), j as u32));
+            acc = acc.add(&B8);
+        }
+        entries.sort_unstable();
+
+        let mut bytes = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&max.to_le_bytes());
+        bytes.extend_from_slice(&step.to_le_bytes());
+        bytes.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+        for (k, j) in entries {
+            bytes.extend_from_slice(&k.to_le_bytes());
+            bytes.extend_from_slice(&j.to_le_bytes());
+        }
+        BsgsTable { max, step, bytes: TableBytes::Owned(bytes) }
+    }
+
+    /// Writes the table to `path`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        File::create(path)?.write_all(&self.bytes)
+    }
+
+    /// Reads a table written by `save` into memory
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<BsgsTable> {
+        let mut bytes = vec![];
+        File::open(path)?.read_to_end(&mut bytes)?;
+        BsgsTable::from_bytes(TableBytes::Owned(bytes))
+    }
+
+    /// Memory-maps a table written by `save`, so only the parts of it that are looked at are read from disk
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn mmap<P: AsRef<Path>>(path: P) -> io::Result<BsgsTable> {
+        let file = File::open(path)?;
+        // Safety: the table file must not be modified while it's mapped. Tables are only ever written once, by `save`
+        let mapped = unsafe { Mmap::map(&file)? };
+        BsgsTable::from_bytes(TableBytes::Mapped(mapped))
+    }
+
+    fn from_bytes(bytes: TableBytes) -> io::Result<BsgsTable> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+        if bytes.len() < HEADER_LEN || &bytes[..8] != MAGIC {
+            return Err(invalid("not a BSGS table"));
+        }
+        let max = read_u64(&bytes, 8);
+        let step = read_u64(&bytes, 16);
+        let count = read_u64(&bytes, 24);
+        let expected_len = count.checked_mul(ENTRY_LEN as u64).and_then(|l| l.checked_add(HEADER_LEN as u64));
+        if count != step || expected_len != Some(bytes.len() as u64) {
+            return Err(invalid("BSGS table is truncated or corrupted"));
+        }
+        if step == 0 || max / step > step {
+            return Err(invalid("BSGS table's step is too small for its max"));
+        }
+        Ok(BsgsTable { max, step, bytes })
+    }
+
+    /// Largest message the table can decode
+    pub fn max(&self) -> u64 {
+        self.max
+    }
+
+    /// Finds m in [0, max] such that `p` = m*B8, if there is one
+    pub fn decode(&self, p: &Point) -> Option<u64> {
+        let giant_step = B8.mul_scalar(&BigInt::from(self.step)).neg();
+        let mut acc = p.clone();
+        for i in 0..(self.max / self.step + 1) {
+            for j in self.candidates(key(&acc)) {
+                let m = i * self.step + j as u64;
+                if m <= self.max && B8.mul_scalar(&BigInt::from(m)).equals(p.clone()) {
+                    return Some(m);
+                }
+            }
+            acc = acc.add(&giant_step);
+        }
+        None
+    }
+
+    /// Decodes every point, in parallel where threads are available
+    pub fn decode_many(&self, points: &[Point]) -> Vec<Option<u64>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return points.par_iter().map(|p| self.decode(p)).collect();
+        #[cfg(target_arch = "wasm32")]
+        return points.iter().map(|p| self.decode(p)).collect();
+    }
+
+    // Every j whose entry has key `k`
+    fn candidates(&self, k: u64) -> impl Iterator<Item = u32> + '_ {
+        let entries = &self.bytes[HEADER_LEN..];
+        let count = entries.len() / ENTRY_LEN;
+        let key_at = |idx: usize| read_u64(entries, idx * ENTRY_LEN);
+        // first entry with key >= k
+        let (mut lo, mut hi) = (0, count);
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if key_at(mid) < k { lo = mid + 1 } else { hi = mid }
+        }
+        (lo..count).take_while(move |idx| key_at(*idx) == k).map(
+            move |idx| u32::from_le_bytes(entries[idx * ENTRY_LEN + 8..idx * ENTRY_LEN + 12].try_into().unwrap())
+        )
+    }
+}
+
+fn identity() -> Point {
+    Point { x: Fr::zero(), y: Fr::one() }
+}
+
+fn key(p: &Point) -> u64 {
+    u64::from_le_bytes(p.compress()[..8].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let table = BsgsTable::build(1000);
+        for m in [0u64, 1, 2, 31, 32, 33, 999, 1000] {
+            assert_eq!(table.decode(&B8.mul_scalar(&BigInt::from(m))), Some(m));
+        }
+        assert_eq!(table.decode(&B8.mul_scalar(&BigInt::from(1001u32))), None);
+
+        let points: Vec<Point> = (0..50u32).map(|m| B8.mul_scalar(&BigInt::from(m * 20))).collect();
+        let decoded = table.decode_many(&points);
+        assert_eq!(decoded, (0..50u64).map(|m| Some(m * 20)).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir().join(format!("bsgs-test-{}.bin", std::process::id()));
+        BsgsTable::build(5000).save(&path).unwrap();
+
+        let loaded = BsgsTable::load(&path).unwrap();
+        let mapped = BsgsTable::mmap(&path).unwrap();
+        for table in [loaded, mapped] {
+            assert_eq!(table.max(), 5000);
+            assert_eq!(table.decode(&B8.mul_scalar(&BigInt::from(4321u32))), Some(4321));
+        }
+
+        // corrupt files are rejected rather than giving wrong answers
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes.truncate(bytes.len() - 1);
+        std::fs::write(&path, &bytes).unwrap();
+        assert!(BsgsTable::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,208 @@
+/*
+Constant-time scalar multiplication for secret scalars
+
+`Point::mul_scalar` branches on the bits of the scalar and converts to affine coordinates with a variable-time inversion, so multiplying an attacker-chosen
+point (e.g. a ciphertext's C1) by a keyshare can leak the keyshare through timing. `mul_scalar_ct` is a Montgomery ladder instead:
+    - it always does NUM_BITS iterations of one addition and one doubling, whatever the scalar
+    - the ladder's two points are swapped by arithmetic (q - p times 0 or 1) rather than by a branch
+    - it uses the complete twisted Edwards addition law in projective coordinates, which has no exceptional cases, so no branch on the points either
+    - the result is made affine by raising Z to the power p - 2, which depends only on the public modulus
+It's only as constant-time as ff_ce's field arithmetic underneath it, which isn't guaranteed to be. `dudect_mul_scalar_ct` in the tests is a timing test
+comparing a fixed random scalar with fresh random ones; run it with `cargo test --release -p babyjubjub-elgamal -- --ignored dudect`.
+*/
+use std::sync::OnceLock;
+use babyjubjub_rs::{Fl, Fr, Point};
+use ff::{Field, PrimeField, PrimeFieldRepr};
+
+type FrRepr = <Fr as PrimeField>::Repr;
+
+// a and d of the curve a*x^2 + y^2 = 1 + d*x^2*y^2
+fn curve_a() -> &'static Fr {
+    static A: OnceLock<Fr> = OnceLock::new();
+    A.get_or_init(|| Fr::from_str("168700").unwrap())
+}
+
+fn curve_d() -> &'static Fr {
+    static D: OnceLock<Fr> = OnceLock::new();
+    D.get_or_init(|| Fr::from_str("168696").unwrap())
+}
+
+// (X : Y : Z) representing the affine point (X/Z, Y/Z)
+#[derive(Clone, Copy)]
+pub(crate) struct ProjectivePoint {
+    x: Fr,
+    y: Fr,
+    z: Fr,
+}
+
+impl ProjectivePoint {
+    pub(crate) fn identity() -> ProjectivePoint {
+        ProjectivePoint { x: Fr::zero(), y: Fr::one(), z: Fr::one() }
+    }
+
+    pub(crate) fn from_affine(p: &Point) -> ProjectivePoint {
+        ProjectivePoint { x: p.x, y: p.y, z: Fr::one() }
+    }
+
+    // Z^-1 is computed as Z^(p-2) so that it takes the same time for every Z
+    pub(crate) fn to_affine(&self) -> Point {
+        let mut exp = Fr::char();
+        exp.sub_noborrow(&FrRepr::from(2));
+        let z_inv = self.z.pow(exp);
+        let mut x = self.x;
+        x.mul_assign(&z_inv);
+        let mut y = self.y;
+        y.mul_assign(&z_inv);
+        Point { x, y }
+    }
+
+    // add-2008-bbjlp, which is complete for Baby Jubjub since a is a square and d isn't. It's also used for doubling
+    pub(crate) fn add(&self, q: &ProjectivePoint) -> ProjectivePoint {
+        let mut a = self.z;
+        a.mul_assign(&q.z);
+        let mut b = a;
+        b.square();
+        let mut c = self.x;
+        c.mul_assign(&q.x);
+        let mut d = self.y;
+        d.mul_assign(&q.y);
+        let mut e = *curve_d();
+        e.mul_assign(&c);
+        e.mul_assign(&d);
+        let mut f = b;
+        f.sub_assign(&e);
+        let mut g = b;
+        g.add_assign(&e);
+
+        // X3 = A*F*((X1+Y1)*(X2+Y2) - C - D)
+        let mut x3 = self.x;
+        x3.add_assign(&self.y);
+        let mut t = q.x;
+        t.add_assign(&q.y);
+        x3.mul_assign(&t);
+        x3.sub_assign(&c);
+        x3.sub_assign(&d);
+        x3.mul_assign(&a);
+        x3.mul_assign(&f);
+
+        // Y3 = A*G*(D - a*C)
+        let mut ac = *curve_a();
+        ac.mul_assign(&c);
+        let mut y3 = d;
+        y3.sub_assign(&ac);
+        y3.mul_assign(&a);
+        y3.mul_assign(&g);
+
+        // Z3 = F*G
+        let mut z3 = f;
+        z3.mul_assign(&g);
+
+        ProjectivePoint { x: x3, y: y3, z: z3 }
+    }
+}
+
+// Swaps p and q if `bit` is 1 and leaves them if it's 0, without branching on it
+fn conditional_swap(p: &mut ProjectivePoint, q: &mut ProjectivePoint, bit: u64) {
+    let bit = Fr::from_repr(FrRepr::from(bit)).unwrap();
+    for (a, b) in [(&mut p.x, &mut q.x), (&mut p.y, &mut q.y), (&mut p.z, &mut q.z)] {
+        let mut t = *b;
+        t.sub_assign(a);
+        t.mul_assign(&bit);
+        a.add_assign(&t);
+        b.sub_assign(&t);
+    }
+}
+
+// Sets p to q if `bit` is 1 and leaves it if it's 0, without branching on it
+pub(crate) fn conditional_assign(p: &mut ProjectivePoint, q: &ProjectivePoint, bit: u64) {
+    let bit = Fr::from_repr(FrRepr::from(bit)).unwrap();
+    for (a, b) in [(&mut p.x, &q.x), (&mut p.y, &q.y), (&mut p.z, &q.z)] {
+        let mut t = *b;
+        t.sub_assign(a);
+        t.mul_assign(&bit);
+        a.add_assign(&t);
+    }
+}
+
+/// `k`*`p` in time that doesn't depend on `k`. Gives the same result as `p.mul_scalar(&k.to_bigint())`
+pub fn mul_scalar_ct(p: &Point, k: &Fl) -> Point {
+    let k = k.into_repr();
+    let limbs = k.as_ref();
+    let mut r0 = ProjectivePoint::identity();
+    let mut r1 = ProjectivePoint::from_affine(p);
+    // r1 = r0 + p throughout. Rather than swapping before and after each step, the swaps of consecutive steps are combined
+    let mut swapped = 0u64;
+    for i in (0..Fl::NUM_BITS as usize).rev() {
+        let bit = (limbs[i / 64] >> (i % 64)) & 1;
+        conditional_swap(&mut r0, &mut r1, swapped ^ bit);
+        swapped = bit;
+        r1 = r0.add(&r1);
+        r0 = r0.add(&r0);
+    }
+    conditional_swap(&mut r0, &mut r1, swapped);
+    r0.to_affine()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Instant;
+    use num_bigint::{BigInt, RandBigInt};
+    use rand::Rng;
+    use babyjubjub_rs::{B8, SUBORDER};
+    use crate::polynomial::to_fl;
+
+    #[test]
+    fn test_matches_mul_scalar() {
+        let p = B8.mul_scalar(&BigInt::from(987654321u32));
+        let mut scalars = vec![BigInt::from(0u8), BigInt::from(1u8), BigInt::from(2u8), &*SUBORDER - 1u8];
+        scalars.extend((0..10).map(|_| rand::thread_rng().gen_bigint_range(&BigInt::from(0u8), &SUBORDER)));
+        for k in scalars {
+            assert!(mul_scalar_ct(&p, &to_fl(&k)).equals(p.mul_scalar(&k)), "wrong result for k = {}", k);
+            assert!(mul_scalar_ct(&B8, &to_fl(&k)).equals(B8.mul_scalar(&k)));
+        }
+
+        // points outside the subgroup work too, as the addition law is complete
+        let small_order = Point { x: Fr::zero(), y: { let mut y = Fr::one(); y.negate(); y } };
+        let k = to_fl(&BigInt::from(3u8));
+        assert!(mul_scalar_ct(&small_order, &k).equals(small_order.clone()));
+    }
+
+    // dudect-style timing test: times mul_scalar_ct with a fixed scalar and with random ones, interleaved at random, and checks Welch's t-statistic
+    // between the two is small. A |t| over 10 is strong evidence the running time depends on the scalar. Slow, and only meaningful in release mode
+    #[test]
+    #[ignore]
+    fn dudect_mul_scalar_ct() {
+        const MEASUREMENTS: usize = 20000;
+        let mut rng = rand::thread_rng();
+        let p = B8.mul_scalar(&BigInt::from(987654321u32));
+        // a full-width scalar, so the fixed class does as many nonzero-bit steps as a typical random one would
+        let fixed = to_fl(&rng.gen_bigint_range(&(&*SUBORDER >> 1), &SUBORDER));
+        let mut classes: [Vec<f64>; 2] = [vec![], vec![]];
+        for _ in 0..MEASUREMENTS {
+            let class = rng.gen_range(0..2usize);
+            let k = if class == 0 { fixed } else { to_fl(&rng.gen_bigint_range(&BigInt::from(0u8), &SUBORDER)) };
+            let start = Instant::now();
+            let result = mul_scalar_ct(&p, &k);
+            classes[class].push(start.elapsed().as_nanos() as f64);
+            std::hint::black_box(result);
+        }
+        // the slowest measurements are mostly interruptions, so leave them out
+        let [fixed_times, random_times] = classes.map(|mut times| {
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            times.truncate(times.len() * 9 / 10);
+            times
+        });
+        let t = welch_t(&fixed_times, &random_times);
+        println!("dudect: t = {:.2} over {} measurements", t, MEASUREMENTS);
+        assert!(t.abs() < 10.0, "timing depends on the scalar: t = {:.2}", t);
+    }
+
+    fn welch_t(a: &[f64], b: &[f64]) -> f64 {
+        let mean = |v: &[f64]| v.iter().sum::<f64>() / v.len() as f64;
+        let var = |v: &[f64], m: f64| v.iter().map(|x| (x - m).powi(2)).sum::<f64>() / (v.len() - 1) as f64;
+        let (ma, mb) = (mean(a), mean(b));
+        (ma - mb) / (var(a, ma) / a.len() as f64 + var(b, mb) / b.len() as f64).sqrt()
+    }
+}
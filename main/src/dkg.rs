@@ -0,0 +1,545 @@
+/*
+Pedersen-style distributed key generation (Gennaro, Jarecki, Krawczyk and Rabin, "Secure Distributed Key Generation for Discrete-Log Based Cryptosystems")
+
+In the simple DKG in lib.rs, every node publishes its pubkey share A_i(0)*B8 and the joint public key is their sum. The last node to publish
+can wait until it has seen everyone else's pubkey share and choose its own so that the sum is whatever it likes (or has some property it likes).
+
+Here the nodes instead commit to their keygen polynomials with Pedersen commitments a_k*B8 + b_k*H, where b_k are the coefficients of a second
+random "blinding" polynomial and H is a second generator nobody knows the discrete log of with respect to B8. These commitments reveal nothing
+about A_i(0), so there is nothing to bias against until everyone's polynomial is fixed.
+
+Sharing round
+-------------
+1. Every node i deals: it broadcasts the Pedersen commitments to its polynomials and privately sends A_i(j) and B_i(j) to node j
+2. Node j checks A_i(j)*B8 + B_i(j)*H against node i's commitments. If it doesn't match (or nothing arrived), node j broadcasts a complaint against i
+3. Node i answers every complaint against it by broadcasting the share it should have sent (a justification)
+4. Everyone disqualifies a node that got at least `threshold_nodes` complaints, or didn't justify one, or whose justification doesn't match its commitments.
+   The remaining nodes are the qualified set, and everyone computes the same one since it only depends on broadcast messages.
+   Only one complaint per accuser counts, and only if the broadcast channel says the accuser sent it, so nobody can complain in another node's name
+   Node j's keyshare is the sum of A_i(j) over the qualified nodes i, where a justification only replaces the share node j got from i
+   if node j complained about i and it matches i's commitments
+
+Extraction round
+----------------
+5. Every qualified node broadcasts Feldman commitments to its keygen polynomial (a `KeygenCommitment`), now that it can no longer change it
+6. Node j checks every share it holds against the Feldman commitments. If one doesn't match, it complains and reveals that share,
+   which everyone can check does match the dealer's Pedersen commitments. This proves the dealer's Feldman commitments are wrong
+7. For every dealer with a valid complaint against it, the other nodes reveal the shares they hold of its polynomial,
+   and A_i(0) is reconstructed in the open by Lagrange interpolation. It was fixed in step 4 anyways, so this doesn't give the dealer any say
+8. The joint public key is the sum of A_i(0)*B8 over the qualified set, using the reconstructed value where there is one
+*/
+use std::{error, fmt, sync::OnceLock};
+use num_bigint::BigInt;
+use babyjubjub_rs::{Fl, Point, B8, FrBigIntConversion};
+use serde::{Serialize, Deserialize};
+use ff::Field;
+
+use crate::{Node, PrivateKeyShare, KeygenCommitment, PublicKey, PubkeyShare, calculate_pubkey};
+use crate::polynomial::{Polynomial, eval_commitments, lagrange_basis_at_0, to_fl};
+use crate::secret::SecretScalar;
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::{mul_b8, mul_b8_ct};
+
+const PEDERSEN_H_DOMAIN: &str = "babyjubjub-elgamal pedersen generator H";
+
+/// Second generator for Pedersen commitments. It is hashed to the curve so that nobody knows its discrete log with respect to B8
+pub fn pedersen_h() -> Point {
+    static H: OnceLock<Point> = OnceLock::new();
+    H.get_or_init(
+        || Point::hash_to_curve_bls(PEDERSEN_H_DOMAIN.as_bytes()).expect("failed to hash the Pedersen generator to the curve")
+    ).clone()
+}
+
+/// Pedersen commitments a_k*B8 + b_k*H to a dealer's keygen polynomial and blinding polynomial, in order of increasing degree
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct PedersenCommitment {
+    pub from_node: usize,
+    pub coefficients: Vec<Point>
+}
+
+impl PedersenCommitment {
+    /// Checks that `share` is the committed polynomials evaluated at `share.for_node`
+    pub fn verify(&self, share: &DealtShare) -> bool {
+        share.from_node == self.from_node &&
+        mul_b8_ct(&to_fl(&share.value)).add(&mul_scalar_ct(&pedersen_h(), &to_fl(&share.blinding))).equals(
+            eval_commitments(&self.coefficients, share.for_node)
+        )
+    }
+}
+
+/// A dealer's keygen polynomial and blinding polynomial evaluated at `for_node`. Sent privately, unless it's revealed as a justification or complaint
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct DealtShare {
+    pub from_node: usize,
+    pub for_node: usize,
+    pub value: BigInt,
+    pub blinding: BigInt
+}
+
+/// Broadcast by `accuser` when the share it got from `against` is missing or doesn't match `against`'s Pedersen commitments.
+/// Functions taking complaints take them with the sender the broadcast channel authenticated, and ignore any whose `accuser` is someone else
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct Complaint {
+    pub accuser: usize,
+    pub against: usize
+}
+
+/// Broadcast in the extraction round when a share matches the dealer's Pedersen commitments but not its Feldman commitments.
+/// The share is revealed so everyone can check the complaint
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct FeldmanComplaint {
+    pub accuser: usize,
+    pub share: DealtShare
+}
+
+/// The result of a successful DKG for one node
+pub struct DkgOutput {
+    /// The node, with its keyshare set, ready to partially decrypt
+    pub node: Node,
+    /// The joint public key
+    pub pubkey: PublicKey,
+    /// Nodes whose polynomials make up the key
+    pub qualified: Vec<usize>,
+    /// Nodes that were caught cheating in the sharing round
+    pub disqualified: Vec<usize>
+}
+
+#[derive(Debug)]
+pub enum DkgError {
+    /// Fewer than `threshold_nodes` nodes were qualified, so the key can't be trusted to need a threshold of honest nodes
+    NotEnoughQualified { qualified: usize, required: usize },
+    /// Not enough valid shares were revealed to reconstruct this dealer's secret after it was caught giving bad Feldman commitments
+    CannotReconstruct(usize),
+    /// This node was given a broadcast message that it should have seen in an earlier round, or a round was skipped
+    MissingShare(usize),
+    /// A step after `resolve_complaints` was called before it
+    ComplaintsNotResolved,
+    /// This dealer's Feldman commitment to its constant term isn't a valid point
+    InvalidCommitment(usize),
+    InvalidNodeIndex { idx: usize, total_nodes: usize },
+    InvalidThreshold { threshold_nodes: usize, total_nodes: usize },
+}
+impl fmt::Display for DkgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DkgError::NotEnoughQualified { qualified, required } => write!(f, "NotEnoughQualified: {} nodes qualified but {} are required", qualified, required),
+            DkgError::CannotReconstruct(n) => write!(f, "CannotReconstruct: not enough valid shares to reconstruct node {}'s secret", n),
+            DkgError::MissingShare(n) => write!(f, "MissingShare: no valid share from qualified node {}", n),
+            DkgError::ComplaintsNotResolved => write!(f, "ComplaintsNotResolved: complaints must be resolved before the extraction round"),
+            DkgError::InvalidCommitment(n) => write!(f, "InvalidCommitment: node {}'s commitment is not on the curve or not in the subgroup", n),
+            DkgError::InvalidNodeIndex { idx, total_nodes } => write!(f, "InvalidNodeIndex: node index {} must be greater than 0 and <= total_nodes {}", idx, total_nodes),
+            DkgError::InvalidThreshold { threshold_nodes, total_nodes } => write!(f, "InvalidThreshold: threshold_nodes {} must be greater than 0 and <= total_nodes {}", threshold_nodes, total_nodes),
+        }
+    }
+}
+impl error::Error for DkgError {}
+
+/// A node participating in the Pedersen DKG. Once the protocol finishes it turns into a regular `Node`
+pub struct DkgNode {
+    idx: usize,
+    threshold_nodes: usize,
+    total_nodes: usize,
+    /// A_i, this node's keygen polynomial
+    keygen_polynomial: Polynomial,
+    /// B_i, used only to blind the commitments to A_i
+    blinding_polynomial: Polynomial,
+    /// every dealer's Pedersen commitments, as received in the sharing round
+    commitments: Vec<PedersenCommitment>,
+    /// shares this node holds that match their dealer's Pedersen commitments
+    shares: Vec<DealtShare>,
+    /// set once complaints are resolved
+    qualified: Option<Vec<usize>>
+}
+
+impl DkgNode {
+    pub fn init_rnd(idx: usize, threshold_nodes: usize, total_nodes: usize) -> Result<DkgNode, DkgError> {
+        if idx == 0 || idx > total_nodes {
+            return Err(DkgError::InvalidNodeIndex { idx, total_nodes });
+        }
+        if threshold_nodes == 0 || threshold_nodes > total_nodes {
+            return Err(DkgError::InvalidThreshold { threshold_nodes, total_nodes });
+        }
+        Ok(DkgNode {
+            idx: idx,
+            threshold_nodes: threshold_nodes,
+            total_nodes: total_nodes,
+            keygen_polynomial: Polynomial::random_polynomial_fl(threshold_nodes-1),
+            blinding_polynomial: Polynomial::random_polynomial_fl(threshold_nodes-1),
+            commitments: vec![],
+            shares: vec![],
+            qualified: None
+        })
+    }
+
+    /// The commitment to broadcast to all nodes
+    pub fn pedersen_commitment(&self) -> PedersenCommitment {
+        PedersenCommitment {
+            from_node: self.idx,
+            coefficients: self.keygen_polynomial.pedersen_commit(&self.blinding_polynomial, &pedersen_h())
+        }
+    }
+
+    fn share_for(&self, node: usize) -> DealtShare {
+        let x = BigInt::from(node);
+        DealtShare {
+            from_node: self.idx,
+            for_node: node,
+            value: self.keygen_polynomial.eval(&x),
+            blinding: self.blinding_polynomial.eval(&x)
+        }
+    }
+
+    /// Step 1: the share to send privately to each node, including this one. Element i is for node i+1
+    pub fn deal(&self) -> Vec<DealtShare> {
+        (1..self.total_nodes+1).map(|j| self.share_for(j)).collect()
+    }
+
+    /// Step 2: checks the shares sent to this node against the broadcast commitments, keeping the good ones. Returns the complaints to broadcast
+    pub fn receive_shares(&mut self, commitments: &Vec<PedersenCommitment>, shares_for_me: &Vec<DealtShare>) -> Vec<Complaint> {
+        self.commitments = commitments.clone();
+        self.shares = vec![];
+        let mut complaints = vec![];
+        for dealer in 1..self.total_nodes+1 {
+            let valid = match (commitment_from(commitments, dealer), shares_for_me.iter().find(|s| s.from_node == dealer)) {
+                (Some(c), Some(s)) => s.for_node == self.idx && c.verify(s),
+                _ => false
+            };
+            if valid {
+                self.shares.push(shares_for_me.iter().find(|s| s.from_node == dealer).unwrap().clone());
+            } else {
+                complaints.push(Complaint { accuser: self.idx, against: dealer });
+            }
+        }
+        complaints
+    }
+
+    /// Step 3: the shares this node has to reveal to answer the complaints against it. `complaints` are the broadcast complaints, each with the node that sent it
+    pub fn justify(&self, complaints: &Vec<(usize, Complaint)>) -> Vec<DealtShare> {
+        authentic_complaints(complaints, self.total_nodes).filter(
+            |c| c.against == self.idx
+        ).map(
+            |c| self.share_for(c.accuser)
+        ).collect()
+    }
+
+    /// Step 4: works out the qualified set from the broadcast complaints and justifications, and adopts the justified shares answering this node's complaints.
+    /// Returns the disqualified nodes
+    pub fn resolve_complaints(&mut self, complaints: &Vec<(usize, Complaint)>, justifications: &Vec<DealtShare>) -> Vec<usize> {
+        let disqualified = disqualified_nodes(&self.commitments, complaints, justifications, self.threshold_nodes, self.total_nodes);
+
+        // a share this node didn't complain about is already verified, so a "justification" for it can only be an attempt to replace it
+        let complained_against: Vec<usize> = authentic_complaints(complaints, self.total_nodes).filter(
+            |c| c.accuser == self.idx
+        ).map(|c| c.against).collect();
+        let adopted: Vec<DealtShare> = justifications.iter().filter(
+            |j| j.for_node == self.idx && complained_against.contains(&j.from_node) && !disqualified.contains(&j.from_node)
+                && commitment_from(&self.commitments, j.from_node).map_or(false, |c| c.verify(j))
+        ).cloned().collect();
+        adopted.into_iter().for_each(|j| {
+            self.shares.retain(|s| s.from_node != j.from_node);
+            self.shares.push(j);
+        });
+
+        self.qualified = Some((1..self.total_nodes+1).filter(|i| !disqualified.contains(i)).collect());
+        disqualified
+    }
+
+    /// Step 5: the Feldman commitments to broadcast once the qualified set is fixed
+    pub fn keygen_commitment(&self) -> KeygenCommitment {
+        KeygenCommitment {
+            from_node: self.idx,
+            coefficients: self.keygen_polynomial.commit()
+        }
+    }
+
+    /// Step 6: checks every share from a qualified dealer against its Feldman commitments. Returns the complaints to broadcast
+    pub fn verify_keygen_commitments(&self, keygen_commitments: &Vec<KeygenCommitment>) -> Result<Vec<FeldmanComplaint>, DkgError> {
+        Ok(self.qualified_shares()?.into_iter().filter(
+            |s| !keygen_commitments.iter().any(
+                |c| c.from_node == s.from_node && c.coefficients.len() == self.threshold_nodes && c.eval(s.for_node).equals(mul_b8_ct(&to_fl(&s.value)))
+            )
+        ).map(
+            |s| FeldmanComplaint { accuser: self.idx, share: s.clone() }
+        ).collect())
+    }
+
+    /// Step 7: this node's shares of the polynomials of dealers that need to be reconstructed
+    pub fn reveal_shares(&self, feldman_complaints: &Vec<FeldmanComplaint>) -> Result<Vec<DealtShare>, DkgError> {
+        let exposed = exposed_dealers(&self.commitments, feldman_complaints);
+        Ok(self.qualified_shares()?.into_iter().filter(
+            |s| exposed.contains(&s.from_node)
+        ).cloned().collect())
+    }
+
+    /// Step 8: computes the joint public key and turns this into a `Node` holding its keyshare
+    pub fn finalize(self, keygen_commitments: &Vec<KeygenCommitment>, feldman_complaints: &Vec<FeldmanComplaint>, revealed_shares: &Vec<DealtShare>) -> Result<DkgOutput, DkgError> {
+        let qualified = self.qualified.clone().ok_or(DkgError::ComplaintsNotResolved)?;
+        if qualified.len() < self.threshold_nodes {
+            return Err(DkgError::NotEnoughQualified { qualified: qualified.len(), required: self.threshold_nodes });
+        }
+        let exposed = exposed_dealers(&self.commitments, feldman_complaints);
+
+        let mut pubkey_shares: Vec<PubkeyShare> = vec![];
+        for dealer in qualified.iter() {
+            if exposed.contains(dealer) {
+                let secret = commitment_from(&self.commitments, *dealer).and_then(|c| reconstruct_secret(c, revealed_shares, self.threshold_nodes))
+                    .ok_or(DkgError::CannotReconstruct(*dealer))?;
+                // revealed by the other nodes, so no longer secret
+                pubkey_shares.push(PubkeyShare::new_unchecked(mul_b8(&secret.to_bigint())));
+            } else {
+                // not exposed, so any node holding a share agreed with this commitment
+                let share = keygen_commitments.iter().filter(|c| c.from_node == *dealer).find_map(|c| c.pubkey_share()).ok_or(DkgError::MissingShare(*dealer))?;
+                pubkey_shares.push(PubkeyShare::new(share).map_err(|_| DkgError::InvalidCommitment(*dealer))?);
+            }
+        }
+
+        let my_shares = self.qualified_shares()?;
+        if let Some(missing) = qualified.iter().find(|q| !my_shares.iter().any(|s| s.from_node == **q)) {
+            return Err(DkgError::MissingShare(*missing));
+        }
+        let keyshare = SecretScalar::sum(my_shares.iter().map(|s| &s.value));
+
+        let at_zero = SecretScalar::new(self.keygen_polynomial.eval_fl(&Fl::zero()));
+        let disqualified = (1..self.total_nodes+1).filter(|i| !qualified.contains(i)).collect();
+        // there are at least threshold_nodes qualified nodes, so this can only fail if threshold_nodes is 0
+        let pubkey = calculate_pubkey(pubkey_shares).ok_or(DkgError::NotEnoughQualified { qualified: qualified.len(), required: self.threshold_nodes })?;
+        Ok(DkgOutput {
+            node: Node {
+                idx: self.idx,
+                threshold_nodes: self.threshold_nodes,
+                total_nodes: self.total_nodes,
+                keygen_polynomial: self.keygen_polynomial,
+                keygen_polynomial_at_0: at_zero,
+                keyshare: Some(PrivateKeyShare { share: keyshare }),
+                reshared: false
+            },
+            pubkey: pubkey,
+            qualified: qualified,
+            disqualified: disqualified
+        })
+    }
+
+    fn qualified_shares(&self) -> Result<Vec<&DealtShare>, DkgError> {
+        let qualified = self.qualified.as_ref().ok_or(DkgError::ComplaintsNotResolved)?;
+        Ok(self.shares.iter().filter(|s| qualified.contains(&s.from_node)).collect())
+    }
+}
+
+fn commitment_from(commitments: &Vec<PedersenCommitment>, dealer: usize) -> Option<&PedersenCommitment> {
+    commitments.iter().find(|c| c.from_node == dealer)
+}
+
+// The complaints that were sent by their accuser, which must be one of the nodes
+fn authentic_complaints(complaints: &Vec<(usize, Complaint)>, total_nodes: usize) -> impl Iterator<Item = &Complaint> {
+    complaints.iter().filter(
+        move |(sender, c)| *sender == c.accuser && c.accuser >= 1 && c.accuser <= total_nodes
+    ).map(|(_, c)| c)
+}
+
+/// Which dealers to disqualify after the sharing round. Only depends on broadcast messages, so every honest node gets the same answer.
+/// `complaints` are the broadcast complaints, each with the node the broadcast channel says sent it
+pub fn disqualified_nodes(commitments: &Vec<PedersenCommitment>, complaints: &Vec<(usize, Complaint)>, justifications: &Vec<DealtShare>, threshold_nodes: usize, total_nodes: usize) -> Vec<usize> {
+    (1..total_nodes+1).filter(|dealer| {
+        let commitment = match commitment_from(commitments, *dealer) {
+            Some(c) if c.coefficients.len() == threshold_nodes => c,
+            _ => return true
+        };
+        // only count one complaint per accuser
+        let mut accusers: Vec<usize> = authentic_complaints(complaints, total_nodes).filter(|c| c.against == *dealer).map(|c| c.accuser).collect();
+        accusers.sort();
+        accusers.dedup();
+        if accusers.len() >= threshold_nodes {
+            return true;
+        }
+        !accusers.iter().all(
+            |accuser| justifications.iter().any(
+                |j| j.from_node == *dealer && j.for_node == *accuser && commitment.verify(j)
+            )
+        )
+    }).collect()
+}
+
+/// Dealers proven to have published Feldman commitments that don't match their Pedersen commitments
+fn exposed_dealers(commitments: &Vec<PedersenCommitment>, feldman_complaints: &Vec<FeldmanComplaint>) -> Vec<usize> {
+    let mut exposed: Vec<usize> = feldman_complaints.iter().filter(
+        |c| c.share.for_node == c.accuser && commitment_from(commitments, c.share.from_node).map_or(false, |p| p.verify(&c.share))
+    ).map(
+        |c| c.share.from_node
+    ).collect();
+    exposed.sort();
+    exposed.dedup();
+    exposed
+}
+
+/// Reconstructs a dealer's A_i(0) from the shares of it that were revealed, using only ones that match its Pedersen commitments
+fn reconstruct_secret(commitment: &PedersenCommitment, revealed_shares: &Vec<DealtShare>, threshold_nodes: usize) -> Option<Fl> {
+    let mut valid: Vec<&DealtShare> = revealed_shares.iter().filter(|s| commitment.verify(s)).collect();
+    valid.sort_by_key(|s| s.for_node);
+    valid.dedup_by_key(|s| s.for_node);
+    if valid.len() < threshold_nodes {
+        return None;
+    }
+    let valid = &valid[0..threshold_nodes];
+    let indices: Vec<u32> = valid.iter().map(|s| s.for_node as u32).collect();
+
+    let mut acc = Fl::zero();
+    for s in valid.iter() {
+        let mut term = lagrange_basis_at_0(s.for_node as u32, &indices);
+        term.mul_assign(&Fl::from_bigint(&s.value));
+        acc.add_assign(&term);
+    }
+    Some(acc)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Runs the DKG, letting `tamper` change the messages of the sharing round before they are delivered and adding `forged_complaints` and
+    // `forged_justifications` to the broadcast complaints and justifications. Returns every node's output
+    fn run_dkg<F>(threshold_nodes: usize, total_nodes: usize, tamper: F, withhold_justifications_from: Option<usize>, bad_feldman_from: Option<usize>, forged_complaints: Vec<(usize, Complaint)>, forged_justifications: Vec<DealtShare>) -> Vec<Result<DkgOutput, DkgError>>
+        where F: Fn(&mut Vec<Vec<DealtShare>>) {
+        let mut nodes: Vec<DkgNode> = (1..total_nodes+1).map(|i| DkgNode::init_rnd(i, threshold_nodes, total_nodes).unwrap()).collect();
+
+        // step 1
+        let commitments: Vec<PedersenCommitment> = nodes.iter().map(|n| n.pedersen_commitment()).collect();
+        let mut dealt: Vec<Vec<DealtShare>> = nodes.iter().map(|n| n.deal()).collect();
+        tamper(&mut dealt);
+
+        // step 2
+        let mut complaints: Vec<(usize, Complaint)> = nodes.iter_mut().flat_map(|n| {
+            let for_me: Vec<DealtShare> = dealt.iter().map(|d| d[n.idx-1].clone()).collect();
+            let idx = n.idx;
+            n.receive_shares(&commitments, &for_me).into_iter().map(move |c| (idx, c))
+        }).collect();
+        complaints.extend(forged_complaints);
+
+        // step 3
+        let mut justifications: Vec<DealtShare> = nodes.iter().filter(
+            |n| Some(n.idx) != withhold_justifications_from
+        ).flat_map(
+            |n| n.justify(&complaints)
+        ).collect();
+        justifications.extend(forged_justifications);
+
+        // step 4
+        nodes.iter_mut().for_each(|n| { n.resolve_complaints(&complaints, &justifications); });
+
+        // step 5
+        let keygen_commitments: Vec<KeygenCommitment> = nodes.iter().map(|n| {
+            let mut c = n.keygen_commitment();
+            if Some(n.idx) == bad_feldman_from {
+                c.coefficients[0] = c.coefficients[0].add(&B8);
+            }
+            c
+        }).collect();
+
+        // steps 6 and 7
+        let feldman_complaints: Vec<FeldmanComplaint> = nodes.iter().flat_map(|n| n.verify_keygen_commitments(&keygen_commitments).unwrap()).collect();
+        let revealed: Vec<DealtShare> = nodes.iter().flat_map(|n| n.reveal_shares(&feldman_complaints).unwrap()).collect();
+
+        // step 8
+        nodes.into_iter().map(|n| n.finalize(&keygen_commitments, &feldman_complaints, &revealed)).collect()
+    }
+
+    // The secret key is the keyshares interpolated at 0
+    fn secret_from_outputs(outputs: &Vec<DkgOutput>) -> Fl {
+        let indices: Vec<u32> = outputs.iter().map(|o| o.node.idx as u32).collect();
+        let mut acc = Fl::zero();
+        outputs.iter().for_each(|o| {
+            let mut term = lagrange_basis_at_0(o.node.idx as u32, &indices);
+            term.mul_assign(o.node.keyshare.as_ref().unwrap().share.expose_secret());
+            acc.add_assign(&term);
+        });
+        acc
+    }
+
+    fn assert_consistent(outputs: Vec<Result<DkgOutput, DkgError>>) -> Vec<DkgOutput> {
+        let outputs: Vec<DkgOutput> = outputs.into_iter().map(|o| o.unwrap()).collect();
+        let secret = secret_from_outputs(&outputs);
+        outputs.iter().for_each(|o| {
+            assert!(o.pubkey.equals(B8.mul_scalar(&secret.to_bigint())));
+            assert_eq!(o.qualified, outputs[0].qualified);
+            assert_eq!(o.disqualified, outputs[0].disqualified);
+        });
+        outputs
+    }
+
+    #[test]
+    fn test_pedersen_h() {
+        let h = pedersen_h();
+        assert!(h.on_curve());
+        assert!(h.in_subgroup());
+        assert!(!h.equals(B8.clone()));
+    }
+
+    #[test]
+    fn test_honest_dkg() {
+        let outputs = assert_consistent(run_dkg(3, 3, |_| {}, None, None, vec![], vec![]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+        assert!(outputs[0].disqualified.is_empty());
+    }
+
+    // A dealer that sends a bad share but answers the complaint correctly stays qualified, and the victim uses the justified share
+    #[test]
+    fn test_justified_complaint() {
+        let outputs = assert_consistent(run_dkg(2, 3, |dealt| { dealt[0][2].value += BigInt::from(1u8); }, None, None, vec![], vec![]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+    }
+
+    #[test]
+    fn test_unjustified_complaint_disqualifies() {
+        let outputs = assert_consistent(run_dkg(2, 3, |dealt| { dealt[0][2].value += BigInt::from(1u8); }, Some(1), None, vec![], vec![]));
+        assert_eq!(outputs[0].qualified, vec![2,3]);
+        assert_eq!(outputs[0].disqualified, vec![1]);
+    }
+
+    // A dealer that publishes Feldman commitments to a different constant term can't move the public key: its secret is reconstructed instead
+    #[test]
+    fn test_bad_feldman_commitment_is_reconstructed() {
+        let outputs = assert_consistent(run_dkg(2, 3, |_| {}, None, Some(2), vec![], vec![]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+    }
+
+    #[test]
+    fn test_too_few_qualified() {
+        // every dealer sends node 1 a bad share and nobody justifies, so nobody is qualified
+        let outputs = run_dkg(2, 2, |dealt| { dealt.iter_mut().for_each(|d| d[0].value += BigInt::from(1u8)); }, Some(1), None, vec![], vec![]);
+        assert!(matches!(outputs[0], Err(DkgError::NotEnoughQualified { .. })));
+    }
+
+    // Complaints in another node's name, or from nodes that don't exist, can't disqualify an honest dealer
+    #[test]
+    fn test_forged_complaints_ignored() {
+        let forged = vec![
+            (3, Complaint { accuser: 2, against: 1 }),
+            (3, Complaint { accuser: 7, against: 1 }),
+            (0, Complaint { accuser: 0, against: 1 }),
+        ];
+        let outputs = assert_consistent(run_dkg(2, 3, |_| {}, None, None, forged, vec![]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+    }
+
+    // A justification only replaces a share if the node complained about it and it matches the dealer's commitments, so nobody can
+    // overwrite a good share or answer a complaint with garbage
+    #[test]
+    fn test_forged_justifications_ignored() {
+        let garbage = |for_node: usize| DealtShare { from_node: 1, for_node: for_node, value: BigInt::from(1234u32), blinding: BigInt::from(5678u32) };
+        // node 2 didn't complain about its share from node 1
+        let outputs = assert_consistent(run_dkg(2, 3, |_| {}, None, None, vec![], vec![garbage(2)]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+        // node 3 did complain, and node 1's real justification comes before the forged one
+        let outputs = assert_consistent(run_dkg(2, 3, |dealt| { dealt[0][2].value += BigInt::from(1u8); }, None, None, vec![], vec![garbage(3)]));
+        assert_eq!(outputs[0].qualified, vec![1,2,3]);
+    }
+
+    #[test]
+    fn test_init_errors() {
+        assert!(matches!(DkgNode::init_rnd(0, 2, 3), Err(DkgError::InvalidNodeIndex { idx: 0, total_nodes: 3 })));
+        assert!(matches!(DkgNode::init_rnd(4, 2, 3), Err(DkgError::InvalidNodeIndex { idx: 4, total_nodes: 3 })));
+        assert!(matches!(DkgNode::init_rnd(1, 0, 3), Err(DkgError::InvalidThreshold { .. })));
+        let node = DkgNode::init_rnd(1, 2, 3).unwrap();
+        assert!(matches!(node.reveal_shares(&vec![]), Err(DkgError::ComplaintsNotResolved)));
+    }
+}
@@ -0,0 +1,147 @@
+/*
+Batch verification of DLEQ proofs
+
+babyjubjub-rs's `DLEQProof` is a Chaum-Pedersen proof that xA = x*A and xB = x*B for the same secret x. The prover commits to kA = k*A and kB = k*B for a random k
+and answers the challenge c (`DLEQProof::challenge`, computed over babyjubjub-rs's own transcript) with s = k + c*x, so `DLEQProof::verify` checks
+s*A = kA + c*xA and s*B = kB + c*xB. The proof, its transcript and its serde encoding are what the OPRF server has always returned, so they are left alone here.
+
+An OPRF client checking several server responses, or a combiner checking dozens of verifiable decryption shares, verifies one proof per response. `batch_verify`
+multiplies each proof's two equations by random 128-bit weights and adds them all into one multi-scalar multiplication (`msm::multi_scalar_mul`) over the proofs'
+public fields, which is the identity if every proof is valid, and otherwise isn't except with probability about 2^-128. Only if it isn't are the proofs checked
+one at a time with `verify`, to find the bad one.
+
+The sum is multiplied by the cofactor 8 before comparing it to the identity, since a random weight is a multiple of a small-order point's order too often for
+small-order components to be caught. So the batch also accepts proofs that only hold up to small-order components, which `verify` would reject. Callers that use
+xA or xB for more than the proof, such as a decryption share or an OPRF output, must check those are in the subgroup, as `decrypt_verified` and the OPRF client do.
+*/
+use num_bigint::{BigInt, RandBigInt};
+use babyjubjub_rs::{Fr, Point, DLEQProof};
+use ff::Field;
+
+use crate::msm::multi_scalar_mul;
+use crate::polynomial::reduce;
+
+const COFACTOR: u8 = 8;
+const WEIGHT_BITS: u64 = 128;
+
+/// Verification of many proofs at once
+pub trait BatchVerify: Sized {
+    /// Checks every proof in `proofs`. If any is invalid, returns the index of the first invalid one
+    fn batch_verify(proofs: &[Self]) -> Result<(), usize>;
+}
+
+impl BatchVerify for DLEQProof {
+    fn batch_verify(proofs: &[DLEQProof]) -> Result<(), usize> {
+        if let Some(i) = proofs.iter().position(|p| !on_curve(p)) {
+            return Err(i);
+        }
+        let mut batch = BatchEquation::new();
+        proofs.iter().for_each(|p| batch.add_proof(p));
+        if batch.holds() {
+            return Ok(());
+        }
+        match proofs.iter().position(|p| !p.verify()) {
+            Some(i) => Err(i),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Whether all of `proof`'s points are on the curve, which `BatchEquation::add_proof` needs
+pub(crate) fn on_curve(proof: &DLEQProof) -> bool {
+    [&proof.A, &proof.B, &proof.xA, &proof.xB, &proof.kA, &proof.kB].iter().all(|p| p.on_curve())
+}
+
+// s*A - kA - c*xA and s*B - kB - c*xB, which should both be the identity
+fn equations(proof: &DLEQProof) -> [Vec<(Point, BigInt)>; 2] {
+    let c = proof.challenge();
+    let minus_one = BigInt::from(-1);
+    [
+        vec![(proof.A.clone(), proof.s.clone()), (proof.kA.clone(), minus_one.clone()), (proof.xA.clone(), -&c)],
+        vec![(proof.B.clone(), proof.s.clone()), (proof.kB.clone(), minus_one), (proof.xB.clone(), -c)],
+    ]
+}
+
+/// A random linear combination of equations ∑ k_j*P_j = identity, checked all at once with a single multi-scalar multiplication.
+/// It checks them up to small-order components, so the points only need to be on the curve
+pub(crate) struct BatchEquation {
+    points: Vec<Point>,
+    scalars: Vec<BigInt>,
+}
+
+impl BatchEquation {
+    pub(crate) fn new() -> BatchEquation {
+        BatchEquation { points: vec![], scalars: vec![] }
+    }
+
+    /// Adds a random multiple of ∑ k_j*P_j for the (P_j, k_j) in `terms`
+    pub(crate) fn add(&mut self, terms: Vec<(Point, BigInt)>) {
+        let weight = BigInt::from(rand::thread_rng().gen_biguint(WEIGHT_BITS));
+        for (p, k) in terms {
+            // reducing mod SUBORDER changes k*P by a small-order point at most, which multiplying by the cofactor in `holds` removes
+            self.scalars.push(reduce(&(k * &weight)));
+            self.points.push(p);
+        }
+    }
+
+    /// Adds both of `proof`'s verification equations. The proof's points must be on the curve
+    pub(crate) fn add_proof(&mut self, proof: &DLEQProof) {
+        let [a, b] = equations(proof);
+        self.add(a);
+        self.add(b);
+    }
+
+    /// Whether the combination is the identity, up to small order. If it is, so is every equation added, with overwhelming probability
+    pub(crate) fn holds(&self) -> bool {
+        multi_scalar_mul(&self.points, &self.scalars).map_or(false, |p| is_small_order(&p))
+    }
+}
+
+fn is_small_order(p: &Point) -> bool {
+    let p = p.mul_scalar(&BigInt::from(COFACTOR));
+    p.x == Fr::zero() && p.y == Fr::one()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::B8;
+    use crate::polynomial::to_fl;
+
+    fn proofs(n: u32) -> Vec<DLEQProof> {
+        (0..n).map(
+            |i| DLEQProof::new(to_fl(&BigInt::from(1234 + i)), B8.clone(), B8.mul_scalar(&BigInt::from(99 + i))).unwrap()
+        ).collect()
+    }
+
+    #[test]
+    fn test_batch_verify() {
+        let mut five = proofs(5);
+        assert_eq!(DLEQProof::batch_verify(&five), Ok(()));
+        assert_eq!(DLEQProof::batch_verify(&[]), Ok(()));
+
+        // the proofs at 2 and 4 claim another proof's output
+        five[2].xB = five[0].xB.clone();
+        five[4].xB = five[0].xB.clone();
+        assert_eq!(DLEQProof::batch_verify(&five), Err(2));
+
+        // enough proofs for a bigger multi-scalar multiplication window
+        let mut many = proofs(40);
+        assert_eq!(DLEQProof::batch_verify(&many), Ok(()));
+        many[37].s = reduce(&(&many[37].s + 1u8));
+        assert_eq!(DLEQProof::batch_verify(&many), Err(37));
+    }
+
+    // The batch accepts exactly the proofs `verify` accepts, as long as nothing has a small-order component
+    #[test]
+    fn test_batch_agrees_with_verify() {
+        let mut proofs = proofs(8);
+        proofs[3].kA = proofs[3].kA.add(&B8);
+        proofs[6].xA = proofs[6].xA.add(&B8);
+        proofs.iter().enumerate().for_each(
+            |(i, p)| assert_eq!(DLEQProof::batch_verify(std::slice::from_ref(p)).is_ok(), p.verify(), "proof {}", i)
+        );
+        assert_eq!(DLEQProof::batch_verify(&proofs), Err(3));
+    }
+}
@@ -0,0 +1,354 @@
+/*
+Compact binary encodings
+
+The serde encodings of points are JSON objects of decimal field element strings, which are far too big to put on chain. These are fixed-size byte encodings instead:
+    Point                           32 bytes: the standard compressed point, y in little-endian with the sign of x in the top bit
+    ElGamalEncryption / Ciphertext  64 bytes: C1 | C2
+    KeygenHelper                    40 bytes: for_node as a little-endian u32 | from_node as a little-endian u32 | value as a 32-byte little-endian scalar
+    DecryptionShare                 36 bytes: node_idx as a little-endian u32 | point
+    RawDecryptionShare              36 bytes: node_idx as a little-endian u32 | share
+    DLEQProof                      224 bytes: A | B | xA | xB | kA | kB | s as a 32-byte little-endian scalar
+    VerifiableDecryptionShare      260 bytes: node_idx as a little-endian u32 | share | proof
+    Tdh2Ciphertext            160 + label bytes: C1 | C2 | C1_bar | e | f | label
+    LabeledCiphertext         160 + label bytes: the same as its Tdh2Ciphertext, whose label must be the `CiphertextLabel::to_bytes` of a label
+
+Every value has exactly one encoding, and decoding is strict: anything that isn't what the encoder would have produced is rejected, including a y that isn't reduced,
+a sign bit set on x = 0 and scalars that aren't reduced mod SUBORDER. Decoded points must be in the subgroup, except C2 of a ciphertext, which only has to be on the curve
+as message points may not be in the subgroup (the same as `Ciphertext::new`), and the points of a babyjubjub-rs DLEQProof, which only have to be on the curve (see `dleq`).
+Only a ciphertext's label makes its encoding variable-size, so it goes last.
+*/
+use num_bigint::{BigInt, Sign};
+use babyjubjub_rs::{Point, ElGamalEncryption, DLEQProof, SUBORDER, decompress_point};
+
+use crate::{Error, KeygenHelper, RawDecryptionShare, Ciphertext, DecryptionShare, VerifiableDecryptionShare, PublicKey, PubkeyShare, check_point};
+use crate::tdh2::{Tdh2Ciphertext, LabeledCiphertext, CiphertextLabel};
+use crate::polynomial::reduce;
+
+pub const POINT_LEN: usize = 32;
+pub const SCALAR_LEN: usize = 32;
+pub const CIPHERTEXT_LEN: usize = 2 * POINT_LEN;
+pub const KEYGEN_HELPER_LEN: usize = 8 + SCALAR_LEN;
+pub const DECRYPTION_SHARE_LEN: usize = 4 + POINT_LEN;
+pub const DLEQ_PROOF_LEN: usize = 6 * POINT_LEN + SCALAR_LEN;
+pub const VERIFIABLE_DECRYPTION_SHARE_LEN: usize = DECRYPTION_SHARE_LEN + DLEQ_PROOF_LEN;
+/// Length of a TDH2 ciphertext's encoding without its label
+pub const TDH2_CIPHERTEXT_LEN: usize = 3 * POINT_LEN + 2 * SCALAR_LEN;
+
+/// Canonical byte encoding, which is fixed-size for everything but ciphertexts with labels
+pub trait CompactEncoding: Sized {
+    fn to_compact(&self) -> Vec<u8>;
+    /// Decodes `bytes`, rejecting anything that isn't the canonical encoding of a valid value
+    fn from_compact(bytes: &[u8]) -> Result<Self, Error>;
+}
+
+impl CompactEncoding for Point {
+    fn to_compact(&self) -> Vec<u8> {
+        self.compress().to_vec()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<Point, Error> {
+        let p = decode_on_curve(bytes)?;
+        check_point(&p)?;
+        Ok(p)
+    }
+}
+
+impl CompactEncoding for ElGamalEncryption {
+    fn to_compact(&self) -> Vec<u8> {
+        [self.c1.compress(), self.c2.compress()].concat()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<ElGamalEncryption, Error> {
+        Ok(Ciphertext::from_compact(bytes)?.into())
+    }
+}
+
+impl CompactEncoding for Ciphertext {
+    fn to_compact(&self) -> Vec<u8> {
+        [self.c1().compress(), self.c2().compress()].concat()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<Ciphertext, Error> {
+        check_len(bytes, CIPHERTEXT_LEN)?;
+        Ciphertext::new(
+            Point::from_compact(&bytes[..POINT_LEN])?,
+            decode_on_curve(&bytes[POINT_LEN..])?
+        )
+    }
+}
+
+impl CompactEncoding for PublicKey {
+    fn to_compact(&self) -> Vec<u8> {
+        self.compress().to_vec()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<PublicKey, Error> {
+        PublicKey::new(Point::from_compact(bytes)?)
+    }
+}
+
+impl CompactEncoding for PubkeyShare {
+    fn to_compact(&self) -> Vec<u8> {
+        self.compress().to_vec()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<PubkeyShare, Error> {
+        PubkeyShare::new(Point::from_compact(bytes)?)
+    }
+}
+
+impl CompactEncoding for KeygenHelper {
+    fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(KEYGEN_HELPER_LEN);
+        bytes.extend_from_slice(&encode_index(self.for_node));
+        bytes.extend_from_slice(&encode_index(self.from_node));
+        bytes.extend_from_slice(&encode_scalar(&self.value));
+        bytes
+    }
+    fn from_compact(bytes: &[u8]) -> Result<KeygenHelper, Error> {
+        check_len(bytes, KEYGEN_HELPER_LEN)?;
+        Ok(KeygenHelper {
+            for_node: decode_index(&bytes[..4])?,
+            from_node: decode_index(&bytes[4..8])?,
+            value: decode_scalar(&bytes[8..])?
+        })
+    }
+}
+
+impl CompactEncoding for DecryptionShare {
+    fn to_compact(&self) -> Vec<u8> {
+        [&encode_index(self.node_idx())[..], &self.point().compress()].concat()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<DecryptionShare, Error> {
+        check_len(bytes, DECRYPTION_SHARE_LEN)?;
+        DecryptionShare::new(decode_index(&bytes[..4])?, Point::from_compact(&bytes[4..])?)
+    }
+}
+
+impl CompactEncoding for RawDecryptionShare {
+    fn to_compact(&self) -> Vec<u8> {
+        [&encode_index(self.node_idx())[..], &self.share().compress()].concat()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<RawDecryptionShare, Error> {
+        check_len(bytes, DECRYPTION_SHARE_LEN)?;
+        RawDecryptionShare::new(decode_index(&bytes[..4])?, Point::from_compact(&bytes[4..])?)
+    }
+}
+
+impl CompactEncoding for DLEQProof {
+    fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(DLEQ_PROOF_LEN);
+        for p in [&self.A, &self.B, &self.xA, &self.xB, &self.kA, &self.kB] {
+            bytes.extend_from_slice(&p.compress());
+        }
+        bytes.extend_from_slice(&encode_scalar(&self.s));
+        bytes
+    }
+    #[allow(non_snake_case)]
+    fn from_compact(bytes: &[u8]) -> Result<DLEQProof, Error> {
+        check_len(bytes, DLEQ_PROOF_LEN)?;
+        let point = |i: usize| decode_on_curve(&bytes[i * POINT_LEN..(i + 1) * POINT_LEN]);
+        Ok(DLEQProof {
+            A: point(0)?,
+            B: point(1)?,
+            xA: point(2)?,
+            xB: point(3)?,
+            kA: point(4)?,
+            kB: point(5)?,
+            s: decode_scalar(&bytes[6 * POINT_LEN..])?
+        })
+    }
+}
+
+impl CompactEncoding for VerifiableDecryptionShare {
+    fn to_compact(&self) -> Vec<u8> {
+        [&encode_index(self.node_idx())[..], &self.share().compress(), &self.proof().to_compact()].concat()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<VerifiableDecryptionShare, Error> {
+        check_len(bytes, VERIFIABLE_DECRYPTION_SHARE_LEN)?;
+        VerifiableDecryptionShare::new(
+            decode_index(&bytes[..4])?,
+            Point::from_compact(&bytes[4..DECRYPTION_SHARE_LEN])?,
+            DLEQProof::from_compact(&bytes[DECRYPTION_SHARE_LEN..])?
+        )
+    }
+}
+
+impl CompactEncoding for Tdh2Ciphertext {
+    fn to_compact(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(TDH2_CIPHERTEXT_LEN + self.label.len());
+        for p in [&self.c1, &self.c2, &self.c1_bar] {
+            bytes.extend_from_slice(&p.compress());
+        }
+        bytes.extend_from_slice(&encode_scalar(&self.e));
+        bytes.extend_from_slice(&encode_scalar(&self.f));
+        bytes.extend_from_slice(&self.label);
+        bytes
+    }
+    fn from_compact(bytes: &[u8]) -> Result<Tdh2Ciphertext, Error> {
+        if bytes.len() < TDH2_CIPHERTEXT_LEN {
+            return Err(invalid(format!("expected at least {} bytes but got {}", TDH2_CIPHERTEXT_LEN, bytes.len())));
+        }
+        let scalars = 3 * POINT_LEN;
+        Ok(Tdh2Ciphertext {
+            c1: Point::from_compact(&bytes[..POINT_LEN])?,
+            c2: decode_on_curve(&bytes[POINT_LEN..2 * POINT_LEN])?,
+            c1_bar: Point::from_compact(&bytes[2 * POINT_LEN..scalars])?,
+            e: decode_scalar(&bytes[scalars..scalars + SCALAR_LEN])?,
+            f: decode_scalar(&bytes[scalars + SCALAR_LEN..TDH2_CIPHERTEXT_LEN])?,
+            label: bytes[TDH2_CIPHERTEXT_LEN..].to_vec()
+        })
+    }
+}
+
+impl CompactEncoding for LabeledCiphertext {
+    fn to_compact(&self) -> Vec<u8> {
+        self.ciphertext.to_compact()
+    }
+    fn from_compact(bytes: &[u8]) -> Result<LabeledCiphertext, Error> {
+        let ciphertext = Tdh2Ciphertext::from_compact(bytes)?;
+        Ok(LabeledCiphertext { label: CiphertextLabel::from_bytes(&ciphertext.label)?, ciphertext })
+    }
+}
+
+fn invalid(msg: String) -> Error {
+    Error::InvalidEncoding(msg)
+}
+
+fn check_len(bytes: &[u8], len: usize) -> Result<(), Error> {
+    if bytes.len() != len {
+        return Err(invalid(format!("expected {} bytes but got {}", len, bytes.len())));
+    }
+    Ok(())
+}
+
+// Decompresses a point, checking the encoding is the one `compress` gives for it. decompress_point accepts e.g. a y >= Q, which would give a second encoding of the same point
+fn decode_on_curve(bytes: &[u8]) -> Result<Point, Error> {
+    check_len(bytes, POINT_LEN)?;
+    let compressed: [u8; POINT_LEN] = bytes.try_into().unwrap();
+    let p = decompress_point(compressed).map_err(invalid)?;
+    if p.compress() != compressed {
+        return Err(invalid("point is not canonically encoded".to_string()));
+    }
+    if !p.on_curve() {
+        return Err(Error::NotOnCurve);
+    }
+    Ok(p)
+}
+
+fn encode_index(idx: usize) -> [u8; 4] {
+    u32::try_from(idx).expect("node index doesn't fit in 4 bytes").to_le_bytes()
+}
+
+fn decode_index(bytes: &[u8]) -> Result<usize, Error> {
+    let idx = u32::from_le_bytes(bytes.try_into().unwrap()) as usize;
+    if idx == 0 {
+        return Err(invalid("node index must be greater than 0".to_string()));
+    }
+    Ok(idx)
+}
+
+/// `s` mod SUBORDER as 32 little-endian bytes
+pub fn encode_scalar(s: &BigInt) -> [u8; SCALAR_LEN] {
+    let (_, le) = reduce(s).to_bytes_le();
+    let mut bytes = [0u8; SCALAR_LEN];
+    bytes[..le.len()].copy_from_slice(&le);
+    bytes
+}
+
+/// Decodes a scalar, which must be less than SUBORDER
+pub fn decode_scalar(bytes: &[u8]) -> Result<BigInt, Error> {
+    check_len(bytes, SCALAR_LEN)?;
+    let s = BigInt::from_bytes_le(Sign::Plus, bytes);
+    if s >= *SUBORDER {
+        return Err(invalid("scalar is not reduced".to_string()));
+    }
+    Ok(s)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::B8;
+    use crate::{encrypt, Node};
+    use crate::tests::init_keyshared_nodes;
+    use crate::tdh2::encrypt_labeled;
+
+    #[test]
+    fn test_round_trips() {
+        let p = B8.mul_scalar(&BigInt::from(123456789u32));
+        let encoded = p.to_compact();
+        assert_eq!(encoded.len(), POINT_LEN);
+        assert!(Point::from_compact(&encoded).unwrap().equals(p.clone()));
+
+        let pubkey = PublicKey::new(p.clone()).unwrap();
+        let encrypted = encrypt(&pubkey, &BigInt::from(7654321u32), &B8);
+        let encoded = encrypted.to_compact();
+        assert_eq!(encoded.len(), CIPHERTEXT_LEN);
+        let decoded = Ciphertext::from_compact(&encoded).unwrap();
+        assert!(decoded.c1().equals(encrypted.c1().clone()) && decoded.c2().equals(encrypted.c2().clone()));
+        assert_eq!(ElGamalEncryption::from(encrypted).to_compact(), encoded);
+
+        let node = Node::init_rnd(2, 2, 3);
+        for helper in node.keygen_step1(3) {
+            let decoded = KeygenHelper::from_compact(&helper.to_compact()).unwrap();
+            assert_eq!((decoded.for_node, decoded.from_node, decoded.value), (helper.for_node, helper.from_node, helper.value));
+        }
+
+        let share = DecryptionShare::new(3, p.clone()).unwrap();
+        let decoded = DecryptionShare::from_compact(&share.to_compact()).unwrap();
+        assert!(decoded.node_idx() == 3 && decoded.point().equals(p));
+
+        let nodes = init_keyshared_nodes(2, 3);
+        let verification_keys: Vec<Point> = nodes.iter().map(|n| n.verification_key().unwrap()).collect();
+        let label = CiphertextLabel { user_id: "user 1".to_string(), purpose: "court order".to_string(), expiry: 2000000000 };
+        let labeled = encrypt_labeled(&pubkey, &B8, &label);
+        let encoded = labeled.to_compact();
+        assert_eq!(encoded.len(), TDH2_CIPHERTEXT_LEN + label.to_bytes().len());
+        let decoded = LabeledCiphertext::from_compact(&encoded).unwrap();
+        assert!(decoded.label == label && decoded.verify());
+        assert_eq!(Tdh2Ciphertext::from_compact(&encoded).unwrap().to_compact(), encoded);
+
+        let share = nodes[1].partial_decrypt_with_proof(&labeled.ciphertext, &vec![1, 2]);
+        let encoded = share.to_compact();
+        assert_eq!(encoded.len(), VERIFIABLE_DECRYPTION_SHARE_LEN);
+        let decoded = VerifiableDecryptionShare::from_compact(&encoded).unwrap();
+        assert!(decoded.verify(&labeled.ciphertext.c1, &verification_keys[1], &vec![1, 2]));
+        assert_eq!(decoded.to_compact(), encoded);
+        assert_eq!(DLEQProof::from_compact(&share.proof().to_compact()).unwrap().to_compact().len(), DLEQ_PROOF_LEN);
+    }
+
+    #[test]
+    fn test_strict_decoding() {
+        let p = B8.mul_scalar(&BigInt::from(123456789u32));
+        let encoded = p.to_compact();
+        assert!(matches!(Point::from_compact(&encoded[1..]), Err(Error::InvalidEncoding(_))));
+
+        // y + Q decompresses to the same point, but isn't its encoding
+        let mut y = encoded.clone();
+        y[31] &= 0x7f;
+        let y_plus_q = BigInt::from_bytes_le(Sign::Plus, &y) + &*babyjubjub_rs::Q;
+        let mut non_canonical = encode_le(&y_plus_q);
+        non_canonical[31] |= encoded[31] & 0x80;
+        assert!(Point::from_compact(&non_canonical).is_err());
+
+        // a point of small order is on the curve but not in the subgroup
+        let small_order = Point::from_compact(&[0u8; 32]);
+        assert!(small_order.is_err());
+
+        assert!(matches!(decode_scalar(&encode_le(&SUBORDER)), Err(Error::InvalidEncoding(_))));
+        let mut zero_idx = KeygenHelper { for_node: 1, from_node: 1, value: BigInt::from(5u8) }.to_compact();
+        zero_idx[..4].copy_from_slice(&[0u8; 4]);
+        assert!(KeygenHelper::from_compact(&zero_idx).is_err());
+
+        // a label with trailing bytes isn't the encoding of any CiphertextLabel
+        let label = CiphertextLabel { user_id: "user 1".to_string(), purpose: "court order".to_string(), expiry: 2000000000 };
+        let mut encoded = encrypt_labeled(&PublicKey::new(p).unwrap(), &B8, &label).to_compact();
+        encoded.push(0);
+        assert!(matches!(LabeledCiphertext::from_compact(&encoded), Err(Error::InvalidEncoding(_))));
+        assert!(LabeledCiphertext::from_compact(&encoded[..TDH2_CIPHERTEXT_LEN - 1]).is_err());
+    }
+
+    fn encode_le(n: &BigInt) -> [u8; 32] {
+        let (_, le) = n.to_bytes_le();
+        let mut bytes = [0u8; 32];
+        bytes[..le.len()].copy_from_slice(&le);
+        bytes
+    }
+}
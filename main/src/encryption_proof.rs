@@ -0,0 +1,152 @@
+/*
+Proof of correct encryption
+
+Shows that an ElGamal encryption (C1, C2) = (r*B8, M + r*PK) encrypts the same message point M as a commitment Com = M + t*H, without revealing M, r or t.
+H is the Pedersen generator from dkg.rs, which nobody knows the discrete log of. Whoever checks the proof only needs the public key, the ciphertext and the commitment,
+so e.g. a contract holding Com can be convinced the escrowed ciphertext contains the committed message. It also proves the encrypter knows r, and so the plaintext.
+
+The encrypter proves knowledge of r and t such that
+    C1 = r*B8
+    C2 - Com = r*PK - t*H
+with a Schnorr-style sigma protocol made non-interactive with Fiat-Shamir:
+1. Pick random k1, k2. A1 = k1*B8, A2 = k1*PK - k2*H
+2. c = H(PK, C1, C2, Com, A1, A2)
+3. z1 = k1 + c*r, z2 = k2 + c*t
+The verifier checks z1*B8 = A1 + c*C1 and z1*PK - z2*H = A2 + c*(C2 - Com)
+*/
+use num_bigint::{BigInt, RandBigInt, Sign};
+use babyjubjub_rs::{Point, B8, SUBORDER};
+use blake2::{Blake2b512, Digest};
+use serde::{Serialize, Deserialize};
+
+use crate::dkg::pedersen_h;
+use crate::polynomial::{reduce, to_fl};
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::{mul_b8, mul_b8_ct};
+use crate::{PublicKey, Ciphertext, encrypt};
+
+const CHALLENGE_DOMAIN: &[u8] = b"babyjubjub-elgamal correct encryption";
+
+/// Proof that an ElGamal encryption encrypts the message in a `commit_message` commitment
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptionProof {
+    pub a1: Point,
+    pub a2: Point,
+    pub z1: BigInt,
+    pub z2: BigInt,
+}
+
+/// An encryption along with a commitment to its message and a proof the two match
+#[derive(Serialize, Deserialize)]
+pub struct ProvenEncryption {
+    pub encrypted: Ciphertext,
+    pub commitment: Point,
+    pub proof: EncryptionProof,
+}
+
+/// Commitment M + blinding*H to message point M. `blinding` must be random and kept secret, or M can be recovered from the commitment
+pub fn commit_message(msg: &Point, blinding: &BigInt) -> Point {
+    msg.add(&mul_scalar_ct(&pedersen_h(), &to_fl(blinding)))
+}
+
+/// Encrypts `msg` to `pubkey` with `nonce`, commits to it with `blinding`, and proves the encryption and commitment are of the same message
+pub fn encrypt_with_proof(pubkey: &PublicKey, msg: &Point, nonce: &BigInt, blinding: &BigInt) -> ProvenEncryption {
+    let encrypted = encrypt(pubkey, nonce, msg);
+    let commitment = commit_message(msg, blinding);
+    let proof = EncryptionProof::new(pubkey, &encrypted, &commitment, nonce, blinding);
+    ProvenEncryption { encrypted, commitment, proof }
+}
+
+impl EncryptionProof {
+    /// Proves `encrypted` was made with `nonce` and encrypts the message committed to in `commitment` with `blinding`
+    pub fn new(pubkey: &PublicKey, encrypted: &Ciphertext, commitment: &Point, nonce: &BigInt, blinding: &BigInt) -> EncryptionProof {
+        let one = BigInt::from(1u8);
+        let k1 = rand::thread_rng().gen_bigint_range(&one, &SUBORDER);
+        let k2 = rand::thread_rng().gen_bigint_range(&one, &SUBORDER);
+        let (k1_fl, k2_fl) = (to_fl(&k1), to_fl(&k2));
+        let a1 = mul_b8_ct(&k1_fl);
+        let a2 = mul_scalar_ct(pubkey, &k1_fl).add(&mul_scalar_ct(&pedersen_h(), &k2_fl).neg());
+        let c = challenge(pubkey, encrypted, commitment, &a1, &a2);
+        EncryptionProof {
+            a1,
+            a2,
+            z1: reduce(&(k1 + &c * nonce)),
+            z2: reduce(&(k2 + &c * blinding)),
+        }
+    }
+
+    /// Checks `encrypted` encrypts the message committed to in `commitment` to `pubkey`
+    /// C2 and the commitment only have to be on the curve, since messages encoded as points (e.g. with `Point::from_msg_vartime`) often aren't in the subgroup,
+    /// but C2 - Com has to be: the message's small-order component cancels out, and a proof for a message that differs by one must be rejected
+    /// The public key and ciphertext were checked when they were constructed
+    pub fn verify(&self, pubkey: &PublicKey, encrypted: &Ciphertext, commitment: &Point) -> bool {
+        for p in [&self.a1, &self.a2] {
+            if !p.on_curve() || !p.in_subgroup() {
+                return false;
+            }
+        }
+        if !commitment.on_curve() {
+            return false;
+        }
+        let c2_minus_commitment = encrypted.c2().add(&commitment.neg());
+        if !c2_minus_commitment.in_subgroup() {
+            return false;
+        }
+        let c = challenge(pubkey, encrypted, commitment, &self.a1, &self.a2);
+        mul_b8(&self.z1).equals(
+            self.a1.add(&encrypted.c1().mul_scalar(&c))
+        ) &&
+        pubkey.mul_scalar(&self.z1).add(&pedersen_h().mul_scalar(&self.z2).neg()).equals(
+            self.a2.add(&c2_minus_commitment.mul_scalar(&c))
+        )
+    }
+}
+
+// H(domain || PK || C1 || C2 || Com || A1 || A2) mod SUBORDER
+fn challenge(pubkey: &Point, encrypted: &Ciphertext, commitment: &Point, a1: &Point, a2: &Point) -> BigInt {
+    let mut h = Blake2b512::new();
+    h.update(CHALLENGE_DOMAIN);
+    for p in [pubkey, encrypted.c1(), encrypted.c2(), commitment, a1, a2] {
+        h.update(p.compress());
+    }
+    BigInt::from_bytes_be(Sign::Plus, &h.finalize()) % &*SUBORDER
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::Fr;
+    use ff::Field;
+
+    #[test]
+    fn test_encryption_proof() {
+        let pubkey = PublicKey::new(B8.mul_scalar(&BigInt::from(987654321u32))).unwrap();
+        let msg = B8.mul_scalar(&BigInt::from(123456789u32));
+        let proven = encrypt_with_proof(&pubkey, &msg, &BigInt::from(7654321u32), &BigInt::from(1111111u32));
+        assert!(proven.proof.verify(&pubkey, &proven.encrypted, &proven.commitment));
+
+        // a different message, public key or ciphertext shouldn't verify
+        let other_commitment = commit_message(&msg.add(&B8), &BigInt::from(1111111u32));
+        assert!(!proven.proof.verify(&pubkey, &proven.encrypted, &other_commitment));
+        assert!(!proven.proof.verify(&PublicKey::new(pubkey.add(&B8)).unwrap(), &proven.encrypted, &proven.commitment));
+        let mauled = Ciphertext::new(proven.encrypted.c1().clone(), proven.encrypted.c2().add(&B8)).unwrap();
+        assert!(!proven.proof.verify(&pubkey, &mauled, &proven.commitment));
+    }
+
+    #[test]
+    fn test_koblitz_encoded_message() {
+        let pubkey = PublicKey::new(B8.mul_scalar(&BigInt::from(987654321u32))).unwrap();
+        // a message whose encoding isn't in the subgroup
+        let msg = (1..100u32).map(|m| Point::from_msg_vartime(&BigInt::from(m))).find(|p| !p.in_subgroup()).unwrap();
+        let proven = encrypt_with_proof(&pubkey, &msg, &BigInt::from(7654321u32), &BigInt::from(1111111u32));
+        assert!(proven.proof.verify(&pubkey, &proven.encrypted, &proven.commitment));
+
+        // the same message plus a point of order 2, (0, -1), in the commitment only
+        let mut minus_one = Fr::one();
+        minus_one.negate();
+        let order_2 = Point { x: Fr::zero(), y: minus_one };
+        let other_commitment = proven.commitment.add(&order_2);
+        assert!(!proven.proof.verify(&pubkey, &proven.encrypted, &other_commitment));
+    }
+}
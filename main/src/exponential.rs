@@ -0,0 +1,94 @@
+/*
+Exponential ElGamal
+
+Messages are encrypted as m*B8 instead of being encoded as a point. Then ciphertexts can be added together to get an encryption of the sum of
+their messages, or multiplied by a scalar to get an encryption of the message times it, which makes it possible to tally encrypted counters:
+    (r1*B8, m1*B8 + r1*PK) + (r2*B8, m2*B8 + r2*PK) = ((r1+r2)*B8, (m1+m2)*B8 + (r1+r2)*PK)
+
+Decryption is the usual threshold decryption, with the nodes' `partial_decrypt_c1`, which gives m*B8. Getting m from that is a discrete log, which is only feasible when m is known to be small.
+It is found with baby-step giant-step in O(sqrt(max)) time and memory, where max is the largest m that is searched for.
+When decoding many messages or with a big max, build a `BsgsTable` once (or load one from disk) and use `decrypt_exponential_with_table`
+*/
+use num_bigint::BigInt;
+use babyjubjub_rs::{Point, ElGamalEncryption};
+
+use crate::{Error, Ciphertext, PublicKey, DecryptionShare, try_decrypt};
+use crate::bsgs::BsgsTable;
+use crate::fixed_base::{mul_b8_ct, encrypt_elgamal};
+use crate::polynomial::to_fl;
+
+/// Homomorphic operations on exponential ElGamal ciphertexts
+pub trait HomomorphicEncryption {
+    /// Encryption of the sum of both ciphertexts' messages
+    fn add_ciphertext(&self, other: &ElGamalEncryption) -> ElGamalEncryption;
+    /// Encryption of the message times `k`. Not constant time, so `k` must be public
+    fn scalar_mul(&self, k: &BigInt) -> ElGamalEncryption;
+}
+
+impl HomomorphicEncryption for ElGamalEncryption {
+    fn add_ciphertext(&self, other: &ElGamalEncryption) -> ElGamalEncryption {
+        ElGamalEncryption { c1: self.c1.add(&other.c1), c2: self.c2.add(&other.c2) }
+    }
+    fn scalar_mul(&self, k: &BigInt) -> ElGamalEncryption {
+        ElGamalEncryption { c1: self.c1.mul_scalar(k), c2: self.c2.mul_scalar(k) }
+    }
+}
+
+/// Encrypts m*B8 to `pubkey` using `nonce`
+pub fn encrypt_exponential(pubkey: &PublicKey, m: u64, nonce: &BigInt) -> ElGamalEncryption {
+    encrypt_elgamal(pubkey, nonce, &mul_b8_ct(&to_fl(&BigInt::from(m))))
+}
+
+/// Threshold decrypts an exponential ElGamal ciphertext, like `decrypt`, then finds m from m*B8, looking for m in [0, max]
+pub fn decrypt_exponential(encrypted: Ciphertext, shares: Vec<DecryptionShare>, num_shares_needed: u64, max: u64) -> Result<u64, Error> {
+    let m_times_b8 = try_decrypt(encrypted, shares, num_shares_needed)?;
+    discrete_log(&m_times_b8, max).ok_or(Error::DiscreteLogNotFound { max })
+}
+
+/// Same as `decrypt_exponential`, looking for m in [0, table.max()] using a precomputed table
+pub fn decrypt_exponential_with_table(encrypted: Ciphertext, shares: Vec<DecryptionShare>, num_shares_needed: u64, table: &BsgsTable) -> Result<u64, Error> {
+    let m_times_b8 = try_decrypt(encrypted, shares, num_shares_needed)?;
+    table.decode(&m_times_b8).ok_or(Error::DiscreteLogNotFound { max: table.max() })
+}
+
+/// Finds m in [0, max] such that `p` = m*B8 with baby-step giant-step, if there is one. This builds a new table every time it's called
+pub fn discrete_log(p: &Point, max: u64) -> Option<u64> {
+    BsgsTable::build(max).decode(p)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::B8;
+    use crate::calculate_pubkey;
+    use crate::tests::init_keyshared_nodes;
+
+    #[test]
+    fn test_discrete_log() {
+        for m in [0u64, 1, 2, 99, 100, 101, 999, 1000] {
+            assert_eq!(discrete_log(&B8.mul_scalar(&BigInt::from(m)), 1000), Some(m));
+        }
+        assert_eq!(discrete_log(&B8.mul_scalar(&BigInt::from(1001u32)), 1000), None);
+        assert_eq!(discrete_log(&B8.mul_scalar(&BigInt::from(123456789u32)), 1000), None);
+    }
+
+    // Tally some encrypted counters and threshold decrypt the total
+    #[test]
+    fn test_tally() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+
+        let total = [5u64, 7, 11].iter().enumerate().map(
+            |(i, m)| encrypt_exponential(&pubkey, *m, &BigInt::from(1000 + i))
+        ).reduce(
+            |a, b| a.add_ciphertext(&b)
+        ).unwrap().scalar_mul(&BigInt::from(2u8));
+
+        let shares = vec![
+            nodes[1].try_partial_decrypt_c1(&total.c1, &vec![2, 3]).unwrap(),
+            nodes[2].try_partial_decrypt_c1(&total.c1, &vec![2, 3]).unwrap(),
+        ];
+        assert_eq!(decrypt_exponential(Ciphertext::try_from(total).unwrap(), shares, 2, 10000).unwrap(), 46);
+    }
+}
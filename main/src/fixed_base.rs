@@ -0,0 +1,150 @@
+/*
+Fixed-base scalar multiplication with precomputed tables
+
+Multiplying B8 (or any point used over and over, such as a joint public key) by a scalar with `Point::mul_scalar` does ~250 doublings and ~125 additions,
+each converted back to affine coordinates. A `FixedBaseTable` stores d*16^j*P for every 4-bit digit d and window j, so k*P is the sum of one table entry
+per 4-bit window of k: 63 projective additions and a single conversion to affine, and no doublings at all.
+The table for B8 is built the first time it's used. `mul_scalar` looks up the entry for each digit directly, which depends on the scalar and so leaks it through the
+cache; it's only for public scalars, such as a proof's response. `mul_scalar_ct` reads all 16 entries of every window and keeps the right one by arithmetic
+instead of by indexing, which is slower but constant time, so it's the one to use for keyshares, key shares sent during keygen, nonces and messages.
+`cargo bench -p babyjubjub-elgamal` compares it with `Point::mul_scalar`.
+*/
+use std::sync::OnceLock;
+use num_bigint::BigInt;
+use babyjubjub_rs::{Fl, Point, ElGamalEncryption, B8};
+use ff::PrimeField;
+
+use crate::{Error, check_point};
+use crate::constant_time::{ProjectivePoint, conditional_assign, mul_scalar_ct};
+use crate::polynomial::{reduce, to_fl};
+
+const WINDOW_BITS: usize = 4;
+const WINDOW_SIZE: usize = 1 << WINDOW_BITS;
+// SUBORDER has 251 bits
+const WINDOWS: usize = (251 + WINDOW_BITS - 1) / WINDOW_BITS;
+
+/// Multiples of a fixed point for fast scalar multiplication by it. Takes about 100KB
+pub struct FixedBaseTable {
+    base: Point,
+    // windows[j][d] = d*16^j*base
+    windows: Vec<[ProjectivePoint; WINDOW_SIZE]>,
+}
+
+impl FixedBaseTable {
+    /// Precomputes the table for `base`, which must be on the curve and in the subgroup
+    pub fn new(base: &Point) -> Result<FixedBaseTable, Error> {
+        check_point(base)?;
+        let mut windows = Vec::with_capacity(WINDOWS);
+        // 16^j*base
+        let mut window_base = ProjectivePoint::from_affine(base);
+        for _ in 0..WINDOWS {
+            let mut window = [ProjectivePoint::identity(); WINDOW_SIZE];
+            for d in 1..WINDOW_SIZE {
+                window[d] = window[d - 1].add(&window_base);
+            }
+            window_base = window[WINDOW_SIZE - 1].add(&window_base);
+            windows.push(window);
+        }
+        Ok(FixedBaseTable { base: base.clone(), windows })
+    }
+
+    pub fn base(&self) -> &Point {
+        &self.base
+    }
+
+    /// `k`*base. `k` is reduced mod SUBORDER first, which doesn't change the result as the base is in the subgroup. Not constant time, so `k` must be public
+    pub fn mul_scalar(&self, k: &BigInt) -> Point {
+        let (_, bytes) = reduce(k).to_bytes_le();
+        let mut acc = ProjectivePoint::identity();
+        for (j, window) in self.windows.iter().enumerate() {
+            let byte = bytes.get(j / 2).copied().unwrap_or(0);
+            let digit = (byte >> ((j % 2) * WINDOW_BITS)) as usize & (WINDOW_SIZE - 1);
+            acc = acc.add(&window[digit]);
+        }
+        acc.to_affine()
+    }
+
+    /// `k`*base in time that doesn't depend on `k`, for secret scalars. Gives the same result as `mul_scalar`
+    pub fn mul_scalar_ct(&self, k: &Fl) -> Point {
+        let k = k.into_repr();
+        let limbs = k.as_ref();
+        let mut acc = ProjectivePoint::identity();
+        for (j, window) in self.windows.iter().enumerate() {
+            let bit = j * WINDOW_BITS;
+            let digit = (limbs[bit / 64] >> (bit % 64)) & (WINDOW_SIZE as u64 - 1);
+            let mut entry = ProjectivePoint::identity();
+            for (d, candidate) in window.iter().enumerate() {
+                // 1 if d == digit and 0 otherwise, without comparing
+                let diff = digit ^ d as u64;
+                let is_digit = 1 ^ ((diff | diff.wrapping_neg()) >> 63);
+                conditional_assign(&mut entry, candidate, is_digit);
+            }
+            acc = acc.add(&entry);
+        }
+        acc.to_affine()
+    }
+}
+
+/// The table for B8, built the first time it's needed
+pub fn b8_table() -> &'static FixedBaseTable {
+    static TABLE: OnceLock<FixedBaseTable> = OnceLock::new();
+    TABLE.get_or_init(|| FixedBaseTable::new(&B8).unwrap())
+}
+
+/// `k`*B8 using the precomputed table. Gives the same result as `B8.mul_scalar(k)`. Not constant time, so `k` must be public
+pub fn mul_b8(k: &BigInt) -> Point {
+    b8_table().mul_scalar(k)
+}
+
+/// `k`*B8 using the precomputed table, in time that doesn't depend on `k`
+pub fn mul_b8_ct(k: &Fl) -> Point {
+    b8_table().mul_scalar_ct(k)
+}
+
+/// Same as `babyjubjub_rs::encrypt_elgamal`, but C1 is computed with the B8 table. The nonce is only multiplied in constant time
+pub fn encrypt_elgamal(pubkey: &Point, nonce: &BigInt, msg: &Point) -> ElGamalEncryption {
+    let nonce = to_fl(nonce);
+    ElGamalEncryption { c1: mul_b8_ct(&nonce), c2: msg.add(&mul_scalar_ct(pubkey, &nonce)) }
+}
+
+/// Same as `encrypt_elgamal` to the public key `pubkey_table.base()`, using its table too. Worth it when encrypting many messages to the same key
+pub fn encrypt_elgamal_with_table(pubkey_table: &FixedBaseTable, nonce: &BigInt, msg: &Point) -> ElGamalEncryption {
+    let nonce = to_fl(nonce);
+    ElGamalEncryption { c1: mul_b8_ct(&nonce), c2: msg.add(&pubkey_table.mul_scalar_ct(&nonce)) }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use babyjubjub_rs::{Fr, SUBORDER};
+    use ff::Field;
+
+    #[test]
+    fn test_matches_mul_scalar() {
+        let mut scalars = vec![BigInt::from(0u8), BigInt::from(1u8), BigInt::from(15u8), BigInt::from(16u8), &*SUBORDER - 1u8];
+        scalars.extend((0..10).map(|_| rand::thread_rng().gen_bigint_range(&BigInt::from(0u8), &SUBORDER)));
+        let pubkey = B8.mul_scalar(&BigInt::from(987654321u32));
+        let pubkey_table = FixedBaseTable::new(&pubkey).unwrap();
+        for k in scalars.iter() {
+            assert!(mul_b8(k).equals(B8.mul_scalar(k)), "wrong result for k = {}", k);
+            assert!(pubkey_table.mul_scalar(k).equals(pubkey.mul_scalar(k)));
+            assert!(mul_b8_ct(&to_fl(k)).equals(B8.mul_scalar(k)));
+            assert!(pubkey_table.mul_scalar_ct(&to_fl(k)).equals(pubkey.mul_scalar(k)));
+        }
+        // scalars are reduced
+        assert!(mul_b8(&(&*SUBORDER + 5u8)).equals(B8.mul_scalar(&BigInt::from(5u8))));
+        assert!(mul_b8(&BigInt::from(-5)).equals(B8.mul_scalar(&(&*SUBORDER - 5u8))));
+
+        let nonce = BigInt::from(7654321u32);
+        let encrypted = encrypt_elgamal(&pubkey, &nonce, &B8);
+        let expected = babyjubjub_rs::encrypt_elgamal(&pubkey, &nonce, &B8);
+        assert!(encrypted.c1.equals(expected.c1.clone()) && encrypted.c2.equals(expected.c2.clone()));
+        let encrypted = encrypt_elgamal_with_table(&pubkey_table, &nonce, &B8);
+        assert!(encrypted.c1.equals(expected.c1) && encrypted.c2.equals(expected.c2));
+
+        let off_curve = Point { x: Fr::one(), y: Fr::one() };
+        assert!(matches!(FixedBaseTable::new(&off_curve), Err(Error::NotOnCurve)));
+    }
+}
@@ -0,0 +1,119 @@
+/*
+Hybrid encryption of byte messages of any length
+
+ElGamal can only encrypt a point, and only small messages can be encoded as points. To encrypt anything bigger, ElGamal is used as a
+key encapsulation mechanism (KEM) instead, and the message itself is encrypted with ChaCha20-Poly1305 under the encapsulated key:
+
+1. The sender picks a random nonce r and computes C1 = r*B8 and the Diffie-Hellman shared secret S = r*PK
+2. The symmetric key is a hash of C1 and S, and the message is encrypted with it, authenticating C1 as associated data
+3. The ciphertext is C1 along with the encrypted message. There is no C2, as S never has to be recovered as a message
+
+Decryption works exactly like for a regular ElGamal ciphertext: the nodes partially decrypt C1, then whoever collects enough decryption shares
+reconstructs S from them and derives the same key. The nodes never see the message or the symmetric key.
+Since every encryption uses a new r, it also gets a new key, so the AEAD nonce can be fixed
+*/
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit, Payload}};
+use blake2::{Blake2b512, Digest};
+use num_bigint::{BigInt, RandBigInt};
+use babyjubjub_rs::{Point, SUBORDER};
+use serde::{Serialize, Deserialize};
+
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+use crate::polynomial::to_fl;
+use crate::{Error, DecryptionError, PublicKey, DecryptionShare, RawDecryptionShare, try_reconstruct_dh_secret, try_combine_raw_shares};
+
+const KDF_DOMAIN: &[u8] = b"babyjubjub-elgamal hybrid encryption key";
+
+/// A byte message encrypted to a public key. `c1` is partially decrypted by the nodes like the `c1` of an `ElGamalEncryption`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HybridCiphertext {
+    pub c1: Point,
+    pub ciphertext: Vec<u8>,
+}
+
+/// Encrypts `msg` to `pubkey` with a random nonce
+pub fn encrypt_bytes(pubkey: &PublicKey, msg: &[u8]) -> HybridCiphertext {
+    let nonce = rand::thread_rng().gen_bigint_range(&BigInt::from(1u8), &SUBORDER);
+    encrypt_bytes_with_nonce(pubkey, &nonce, msg)
+}
+
+// Encrypts `msg` to `pubkey` using `nonce` as the ElGamal nonce. The nonce determines the symmetric key, and the ChaCha20 nonce is always zero,
+// so a reused nonce would reuse the key stream. That's why only `encrypt_bytes`, with a fresh random nonce, is public
+fn encrypt_bytes_with_nonce(pubkey: &PublicKey, nonce: &BigInt, msg: &[u8]) -> HybridCiphertext {
+    let nonce = to_fl(nonce);
+    let c1 = mul_b8_ct(&nonce);
+    let dh_secret = mul_scalar_ct(pubkey, &nonce);
+    let ciphertext = cipher(&c1, &dh_secret).encrypt(
+        &Nonce::default(),
+        Payload { msg: msg, aad: &c1.compress() }
+    ).expect("ChaCha20-Poly1305 encryption failed");
+    HybridCiphertext { c1, ciphertext }
+}
+
+/// Decrypts using decryption shares of `encrypted.c1` from `num_shares_needed` nodes, as returned by `Node::partial_decrypt_c1`. Hybrid ciphertexts have no proof of knowledge of the nonce, so only ask nodes to decrypt ones you trust
+pub fn decrypt_bytes(encrypted: &HybridCiphertext, shares: Vec<DecryptionShare>, num_shares_needed: u64) -> Result<Vec<u8>, Error> {
+    let dh_secret = try_reconstruct_dh_secret(shares, num_shares_needed)?;
+    open(encrypted, &dh_secret)
+}
+
+/// Decrypts using the first `threshold_nodes` raw decryption shares of `encrypted.c1`, as returned by `Node::raw_partial_decrypt_c1`
+pub fn decrypt_bytes_raw(encrypted: &HybridCiphertext, shares: &Vec<RawDecryptionShare>, threshold_nodes: usize) -> Result<Vec<u8>, Error> {
+    if shares.len() < threshold_nodes {
+        return Err(Error::Decryption(DecryptionError::WrongNumberOfShares { provided: shares.len(), required: threshold_nodes }));
+    }
+    let dh_secret = try_combine_raw_shares(&shares[..threshold_nodes])?;
+    open(encrypted, &dh_secret)
+}
+
+fn open(encrypted: &HybridCiphertext, dh_secret: &Point) -> Result<Vec<u8>, Error> {
+    cipher(&encrypted.c1, dh_secret).decrypt(
+        &Nonce::default(),
+        Payload { msg: &encrypted.ciphertext, aad: &encrypted.c1.compress() }
+    ).map_err(|_| Error::InvalidCiphertext)
+}
+
+// The symmetric key is the first 32 bytes of H(domain || C1 || S)
+fn cipher(c1: &Point, dh_secret: &Point) -> ChaCha20Poly1305 {
+    let mut h = Blake2b512::new();
+    h.update(KDF_DOMAIN);
+    h.update(c1.compress());
+    h.update(dh_secret.compress());
+    let digest = h.finalize();
+    ChaCha20Poly1305::new(Key::from_slice(&digest[..32]))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_pubkey;
+    use crate::tests::init_keyshared_nodes;
+
+    #[test]
+    fn test_hybrid_threshold_decrypt() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        // longer than a single point could hold
+        let msg: Vec<u8> = (0..1000).map(|i| (i % 251) as u8).collect();
+        let encrypted = encrypt_bytes(&pubkey, &msg);
+
+        let decrypting = vec![1, 3];
+        let shares: Vec<DecryptionShare> = decrypting.iter().map(|i| nodes[*i as usize - 1].try_partial_decrypt_c1(&encrypted.c1, &decrypting).unwrap()).collect();
+        assert_eq!(decrypt_bytes(&encrypted, shares, 2).unwrap(), msg);
+
+        let raw_shares: Vec<RawDecryptionShare> = nodes[1..].iter().map(|n| n.try_raw_partial_decrypt_c1(&encrypted.c1).unwrap()).collect();
+        assert_eq!(decrypt_bytes_raw(&encrypted, &raw_shares, 2).unwrap(), msg);
+    }
+
+    #[test]
+    fn test_hybrid_rejects_tampering() {
+        let nodes = init_keyshared_nodes(2, 2);
+        let pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let mut encrypted = encrypt_bytes(&pubkey, b"some identity document");
+        encrypted.ciphertext[0] ^= 1;
+
+        let shares: Vec<DecryptionShare> = nodes.iter().map(|n| n.try_partial_decrypt_c1(&encrypted.c1, &vec![1, 2]).unwrap()).collect();
+        assert!(matches!(decrypt_bytes(&encrypted, shares, 2), Err(Error::InvalidCiphertext)));
+    }
+}
@@ -0,0 +1,275 @@
+/*
+Encrypted keystore for Node state
+
+A `Node`'s serialized form (`Node::exposed`) has its keygen polynomial and keyshare in the clear, so it shouldn't be written anywhere as is. A keystore is a JSON file:
+    { "version": 1, "kdf": { "type": "argon2id", "salt": hex, "m_cost": .., "t_cost": .., "p_cost": .. } | { "type": "kek" }, "nonce": hex, "ciphertext": hex }
+The ciphertext is the node's serde JSON encrypted with ChaCha20-Poly1305, using the version and kdf as associated data so they can't be changed without detection.
+The key is either derived from a password with Argon2id, or is a 32-byte key-encryption key (KEK) supplied directly, e.g. by a KMS.
+
+Nodes saved before keystores existed are plain serde JSON. `migrate_file` replaces such a file with a keystore of the same node.
+Keystores are written to a temporary file next to the destination, synced, and renamed over it, so a crash never leaves a half-written file
+in place of the only copy of a node. On Unix the file is only readable by its owner. Derived keys and plaintext node JSON are zeroized when dropped
+*/
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use argon2::{Argon2, Algorithm, Version, Params};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce, aead::{Aead, KeyInit, Payload}};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
+
+use crate::{Node, Error};
+
+pub const KEYSTORE_VERSION: u32 = 1;
+/// Argon2id parameters used by `encrypt_node`: 19 MiB, 2 passes, 1 lane
+pub const DEFAULT_M_COST: u32 = 19456;
+pub const DEFAULT_T_COST: u32 = 2;
+pub const DEFAULT_P_COST: u32 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+/// How the keystore's encryption key is obtained
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Kdf {
+    /// Derived from a password with Argon2id. `salt` is hex
+    Argon2id { salt: String, m_cost: u32, t_cost: u32, p_cost: u32 },
+    /// A 32-byte key-encryption key is used as is
+    Kek,
+}
+
+/// A `Node` encrypted at rest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedNode {
+    pub version: u32,
+    pub kdf: Kdf,
+    /// hex
+    pub nonce: String,
+    /// hex
+    pub ciphertext: String,
+}
+
+// The parts of a keystore that are authenticated but not encrypted
+#[derive(Serialize)]
+struct Header<'a> {
+    version: u32,
+    kdf: &'a Kdf,
+}
+
+/// Encrypts `node` with a key derived from `password` using the default Argon2id parameters
+pub fn encrypt_node(node: &Node, password: &[u8]) -> Result<EncryptedNode, Error> {
+    encrypt_node_with_params(node, password, DEFAULT_M_COST, DEFAULT_T_COST, DEFAULT_P_COST)
+}
+
+/// Same as `encrypt_node` with the given Argon2id memory cost in KiB, number of passes and parallelism
+pub fn encrypt_node_with_params(node: &Node, password: &[u8], m_cost: u32, t_cost: u32, p_cost: u32) -> Result<EncryptedNode, Error> {
+    let mut salt = [0u8; SALT_LEN];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let kdf = Kdf::Argon2id { salt: hex::encode(salt), m_cost, t_cost, p_cost };
+    let key = derive_key(&kdf, password)?;
+    seal(node, kdf, &key)
+}
+
+/// Encrypts `node` with a key-encryption key
+pub fn encrypt_node_with_kek(node: &Node, kek: &[u8; 32]) -> Result<EncryptedNode, Error> {
+    seal(node, Kdf::Kek, kek)
+}
+
+impl EncryptedNode {
+    /// Decrypts a keystore made with `encrypt_node` or `encrypt_node_with_params`
+    pub fn decrypt(&self, password: &[u8]) -> Result<Node, Error> {
+        if let Kdf::Kek = self.kdf {
+            return Err(Error::Keystore("keystore is encrypted with a KEK, not a password".to_string()));
+        }
+        let key = derive_key(&self.kdf, password)?;
+        self.open(&key)
+    }
+
+    /// Decrypts a keystore made with `encrypt_node_with_kek`
+    pub fn decrypt_with_kek(&self, kek: &[u8; 32]) -> Result<Node, Error> {
+        if let Kdf::Argon2id { .. } = self.kdf {
+            return Err(Error::Keystore("keystore is encrypted with a password, not a KEK".to_string()));
+        }
+        self.open(kek)
+    }
+
+    /// Writes the keystore to `path` as JSON, replacing any file there atomically
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        write_atomic(path.as_ref(), &serde_json::to_vec_pretty(self)?)
+    }
+
+    /// Reads a keystore written by `save`. Plaintext node files aren't accepted; use `migrate_file` on them first
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<EncryptedNode> {
+        let keystore: EncryptedNode = serde_json::from_slice(&fs::read(path)?)?;
+        Ok(keystore)
+    }
+
+    fn open(&self, key: &[u8; 32]) -> Result<Node, Error> {
+        if self.version != KEYSTORE_VERSION {
+            return Err(Error::Keystore(format!("unsupported keystore version {}", self.version)));
+        }
+        let nonce = decode_hex(&self.nonce, "nonce")?;
+        if nonce.len() != NONCE_LEN {
+            return Err(Error::Keystore(format!("nonce must be {} bytes", NONCE_LEN)));
+        }
+        let ciphertext = decode_hex(&self.ciphertext, "ciphertext")?;
+        let plaintext = Zeroizing::new(ChaCha20Poly1305::new(Key::from_slice(key)).decrypt(
+            Nonce::from_slice(&nonce),
+            Payload { msg: &ciphertext, aad: &header(self.version, &self.kdf) }
+        ).map_err(|_| Error::Keystore("wrong password or key, or the keystore has been modified".to_string()))?);
+        serde_json::from_slice(&plaintext).map_err(|e| Error::Keystore(format!("invalid node: {}", e)))
+    }
+}
+
+/// Replaces the plaintext serde JSON node at `path` with a keystore of it encrypted with `password`. Fails if the file isn't a plaintext node.
+/// Copies of the plaintext file elsewhere, e.g. in backups, still have to be deleted
+pub fn migrate_file<P: AsRef<Path>>(path: P, password: &[u8]) -> Result<EncryptedNode, Error> {
+    let contents = Zeroizing::new(fs::read(&path).map_err(|e| Error::Keystore(e.to_string()))?);
+    if serde_json::from_slice::<EncryptedNode>(&contents).is_ok() {
+        return Err(Error::Keystore("file is already a keystore".to_string()));
+    }
+    let node: Node = serde_json::from_slice(&contents).map_err(|e| Error::Keystore(format!("not a plaintext node: {}", e)))?;
+    let keystore = encrypt_node(&node, password)?;
+    keystore.save(&path).map_err(|e| Error::Keystore(e.to_string()))?;
+    Ok(keystore)
+}
+
+fn seal(node: &Node, kdf: Kdf, key: &[u8; 32]) -> Result<EncryptedNode, Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let plaintext = Zeroizing::new(serde_json::to_vec(&node.exposed()).map_err(|e| Error::Keystore(e.to_string()))?);
+    let ciphertext = ChaCha20Poly1305::new(Key::from_slice(key)).encrypt(
+        Nonce::from_slice(&nonce),
+        Payload { msg: &plaintext, aad: &header(KEYSTORE_VERSION, &kdf) }
+    ).expect("ChaCha20-Poly1305 encryption failed");
+    Ok(EncryptedNode {
+        version: KEYSTORE_VERSION,
+        kdf,
+        nonce: hex::encode(nonce),
+        ciphertext: hex::encode(ciphertext),
+    })
+}
+
+fn derive_key(kdf: &Kdf, password: &[u8]) -> Result<Zeroizing<[u8; 32]>, Error> {
+    match kdf {
+        Kdf::Argon2id { salt, m_cost, t_cost, p_cost } => {
+            let params = Params::new(*m_cost, *t_cost, *p_cost, Some(32)).map_err(|e| Error::Keystore(e.to_string()))?;
+            let mut key = Zeroizing::new([0u8; 32]);
+            Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+                .hash_password_into(password, &decode_hex(salt, "salt")?, &mut *key)
+                .map_err(|e| Error::Keystore(e.to_string()))?;
+            Ok(key)
+        },
+        Kdf::Kek => Err(Error::Keystore("a KEK keystore has no key derivation".to_string())),
+    }
+}
+
+// Writes `contents` to a temporary file in the same directory as `path`, syncs it and renames it over `path`, then syncs the directory so the rename is durable
+fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let file_name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "keystore path has no file name"))?;
+    let mut tmp_name = file_name.to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(&tmp_path)?;
+    // the mode only applies to new files, so a temporary file left over from a crash is fixed up too
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)?;
+    #[cfg(unix)]
+    {
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        fs::File::open(dir)?.sync_all()?;
+    }
+    Ok(())
+}
+
+fn header(version: u32, kdf: &Kdf) -> Vec<u8> {
+    serde_json::to_vec(&Header { version, kdf }).unwrap()
+}
+
+fn decode_hex(s: &str, what: &str) -> Result<Vec<u8>, Error> {
+    hex::decode(s).map_err(|e| Error::Keystore(format!("invalid {}: {}", what, e)))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::init_keyshared_nodes;
+
+    // Cheap Argon2id parameters so the tests are fast
+    fn encrypt_for_test(node: &Node, password: &[u8]) -> EncryptedNode {
+        encrypt_node_with_params(node, password, 64, 1, 1).unwrap()
+    }
+
+    #[test]
+    fn test_password_round_trip() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let keystore = encrypt_for_test(&nodes[1], b"correct horse");
+        let decrypted = keystore.decrypt(b"correct horse").unwrap();
+        assert_eq!(serde_json::to_string(&decrypted.exposed()).unwrap(), serde_json::to_string(&nodes[1].exposed()).unwrap());
+        assert!(matches!(keystore.decrypt(b"battery staple"), Err(Error::Keystore(_))));
+        assert!(matches!(keystore.decrypt_with_kek(&[0u8; 32]), Err(Error::Keystore(_))));
+
+        // the KDF parameters are authenticated
+        let mut weakened = keystore.clone();
+        if let Kdf::Argon2id { ref mut t_cost, .. } = weakened.kdf {
+            *t_cost = 2;
+        }
+        assert!(weakened.decrypt(b"correct horse").is_err());
+    }
+
+    #[test]
+    fn test_kek_round_trip() {
+        let nodes = init_keyshared_nodes(2, 2);
+        let kek = [42u8; 32];
+        let keystore = encrypt_node_with_kek(&nodes[0], &kek).unwrap();
+        let decrypted = keystore.decrypt_with_kek(&kek).unwrap();
+        assert_eq!(serde_json::to_string(&decrypted.exposed()).unwrap(), serde_json::to_string(&nodes[0].exposed()).unwrap());
+        assert!(keystore.decrypt_with_kek(&[43u8; 32]).is_err());
+
+        let mut future = keystore.clone();
+        future.version = 2;
+        assert!(matches!(future.decrypt_with_kek(&kek), Err(Error::Keystore(_))));
+    }
+
+    #[test]
+    fn test_migrate_file() {
+        let nodes = init_keyshared_nodes(2, 2);
+        let path = std::env::temp_dir().join(format!("keystore-test-{}.json", std::process::id()));
+        fs::write(&path, serde_json::to_vec(&nodes[0].exposed()).unwrap()).unwrap();
+        assert!(EncryptedNode::load(&path).is_err());
+
+        migrate_file(&path, b"correct horse").unwrap();
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("keyshare"));
+        let decrypted = EncryptedNode::load(&path).unwrap().decrypt(b"correct horse").unwrap();
+        assert_eq!(serde_json::to_string(&decrypted.exposed()).unwrap(), serde_json::to_string(&nodes[0].exposed()).unwrap());
+
+        // written through a temporary file, which is gone, and only readable by its owner
+        let mut tmp_name = path.file_name().unwrap().to_os_string();
+        tmp_name.push(".tmp");
+        assert!(!path.with_file_name(tmp_name).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        assert!(migrate_file(&path, b"correct horse").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
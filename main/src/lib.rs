@@ -1,12 +1,36 @@
-use num_bigint::{BigInt};
+use num_bigint::{BigInt, RandBigInt};
 use num_traits::{ToPrimitive, FromPrimitive};
-use babyjubjub_rs::{Fl, Point, ElGamalEncryption, B8, FrBigIntConversion};
+use babyjubjub_rs::{Fl, Fr, Point, B8, SUBORDER, FrBigIntConversion, DLEQProof};
 use polynomial::Polynomial;
 use serde::{Serialize, Deserialize};
-use crate::polynomial::lagrange_basis_at_0;
+use crate::polynomial::{lagrange_basis_at_0, eval_commitments, to_fl};
+use crate::secret::SecretScalar;
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+use crate::msm::{multi_scalar_mul, sum_points};
+use crate::dleq::BatchEquation;
+use crate::tdh2::Tdh2Ciphertext;
 use ff::{Field};
+use std::{error, fmt};
 
 mod polynomial;
+pub mod dkg;
+pub mod hybrid;
+pub mod tdh2;
+pub mod encryption_proof;
+pub mod multi_recipient;
+pub mod exponential;
+pub mod bsgs;
+pub mod encoding;
+pub mod keystore;
+mod types;
+pub mod secret;
+pub mod constant_time;
+pub mod fixed_base;
+pub mod msm;
+pub mod dleq;
+
+pub use types::{PublicKey, PubkeyShare, DecryptionShare, Ciphertext, encrypt};
 
 /* 
 HOW THIS WORKS
@@ -42,6 +66,13 @@ Essentially, the nodes just send their evaluations at point i to node i, then no
 
 Now each node has A(i), they can use A(i), their decryption share, to decrypt messages together.
 
+Verifying the evaluations
+-------------------------
+A dishonest node could send evaluations that aren't on a single polynomial. To catch this, every node j also broadcasts
+Feldman commitments to A_j: each coefficient of A_j times the base point. Since A_j(i)*B can be computed from the commitments alone,
+node i checks every A_j(i) it receives against node j's commitments before summing them, and rejects the keyshare naming node j if one doesn't match.
+The commitment to the constant term is node j's pubkey share.
+
 
 Decryption
 ----------------
@@ -95,15 +126,16 @@ babyjubjub-rs library.
 
 */
 
-#[derive(Serialize,Deserialize)]
+#[derive(Deserialize)]
 pub struct PrivateKeyShare {
-    share: BigInt,
+    #[serde(deserialize_with = "crate::secret::expose::deserialize")]
+    share: SecretScalar,
 }
 
 
 impl PrivateKeyShare {
     pub fn from_bigint(b: BigInt) -> PrivateKeyShare {
-        PrivateKeyShare { share: b }
+        PrivateKeyShare { share: SecretScalar::from_bigint(&b) }
     }
 
     // /// C1 is the first value of the (C1, C2) ElGamal encryption result
@@ -115,8 +147,9 @@ impl PrivateKeyShare {
 }
 
 
-/// Node representing a party that can do distributed key generation, store their key share, and calculate a decryption share of a ciphertext using their keyshare 
-#[derive(Serialize,Deserialize)]
+/// Node representing a party that can do distributed key generation, store their key share, and calculate a decryption share of a ciphertext using their keyshare.
+/// It doesn't implement Serialize, since that would write its secrets in the clear: serialize `exposed()` instead, where that's really what's wanted
+#[derive(Deserialize)]
 pub struct Node {
     /// which number node is it. Starts at 1, not 0, as node 0 doesn't exist. If it did, it would know the secret polynomial evaluated at 0, which is the secret key
     idx: usize, 
@@ -125,50 +158,422 @@ pub struct Node {
     /// how many nodes there will be total, so that this node knows how many parties to do key generation with
     total_nodes: usize, 
     /// polynomial used to generate the distributed key
+    #[serde(deserialize_with = "crate::polynomial::expose::deserialize")]
     keygen_polynomial: Polynomial,
     /// `keygen_polynomial`'s evaluation at 0 (used to generate the distributed key)
-    keygen_polynomial_at_0: BigInt,
+    #[serde(deserialize_with = "crate::secret::expose::deserialize")]
+    keygen_polynomial_at_0: SecretScalar,
     /// share of the decryption key
-    keyshare: Option<PrivateKeyShare>
+    keyshare: Option<PrivateKeyShare>,
+    /// whether the node was made by `init_from_reshare`, in which case its keygen polynomial is all zero and it has no pubkey share
+    #[serde(default)]
+    reshared: bool
+}
+
+/// A `Node` with its keygen polynomial and keyshare in the clear, as returned by `Node::exposed`. It serializes to what `Node` deserializes from.
+/// Only serialize it into something as well protected as the node itself, such as a keystore's plaintext
+#[derive(Serialize)]
+pub struct ExposedNode<'a> {
+    idx: usize,
+    threshold_nodes: usize,
+    total_nodes: usize,
+    #[serde(serialize_with = "expose_polynomial")]
+    keygen_polynomial: &'a Polynomial,
+    #[serde(serialize_with = "expose_secret")]
+    keygen_polynomial_at_0: &'a SecretScalar,
+    keyshare: Option<ExposedKeyshare<'a>>,
+    reshared: bool
+}
+
+// Serialized form of PrivateKeyShare
+#[derive(Serialize)]
+struct ExposedKeyshare<'a> {
+    #[serde(serialize_with = "expose_secret")]
+    share: &'a SecretScalar,
+}
+
+fn expose_polynomial<S: serde::Serializer>(p: &&Polynomial, serializer: S) -> Result<S::Ok, S::Error> {
+    polynomial::expose::serialize(p, serializer)
+}
+
+fn expose_secret<S: serde::Serializer>(s: &&SecretScalar, serializer: S) -> Result<S::Ok, S::Error> {
+    secret::expose::serialize(s, serializer)
 }
 
 // Stores a secret number designated for a particular node
 #[derive(Debug,Serialize,Deserialize)]
 pub struct KeygenHelper {
     pub for_node: usize,
+    /// 0 if the helper was stored before senders were recorded, until `fill_legacy_senders` sets it
+    #[serde(default)]
+    pub from_node: usize,
     pub value: BigInt
 }
 
+/// Parses a JSON array of the KeygenHelpers sent to one node, accepting helpers stored before `from_node` was added (see `fill_legacy_senders`)
+pub fn keygen_helpers_from_json(json: &str) -> Result<Vec<KeygenHelper>, serde_json::Error> {
+    let mut helpers: Vec<KeygenHelper> = serde_json::from_str(json)?;
+    fill_legacy_senders(&mut helpers);
+    Ok(helpers)
+}
+
+/// Sets the sender of every helper without one to its position + 1, i.e. takes the helpers sent to a node to be in order of the nodes that sent them,
+/// which is how they were collected before senders were recorded. Helpers that already have a sender are left as they are
+pub fn fill_legacy_senders(helpers: &mut [KeygenHelper]) {
+    helpers.iter_mut().enumerate().filter(|(_, h)| h.from_node == 0).for_each(|(i, h)| h.from_node = i + 1);
+}
+
+/// Feldman commitments to a node's keygen polynomial A_j: every coefficient of A_j times B8, in order of increasing degree.
+/// Node j broadcasts the same commitment to every node, so each node can check the KeygenHelper it was sent is really A_j(i)
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub struct KeygenCommitment {
+    pub from_node: usize,
+    pub coefficients: Vec<Point>
+}
+
+impl KeygenCommitment {
+    /// A_j(x)*B8, computed from the commitments alone as ∑ x^k * (a_k*B8)
+    pub fn eval(&self, x: usize) -> Point {
+        eval_commitments(&self.coefficients, x)
+    }
+
+    /// The committed polynomial's constant term times B8, i.e. the sender's pubkey share. `None` if the commitment has no coefficients
+    pub fn pubkey_share(&self) -> Option<Point> {
+        self.coefficients.first().cloned()
+    }
+
+    /// Checks that `helper` is the committed polynomial evaluated at `helper.for_node`
+    pub fn verify(&self, helper: &KeygenHelper) -> bool {
+        helper.from_node == self.from_node &&
+        mul_b8_ct(&to_fl(&helper.value)).equals(self.eval(helper.for_node))
+    }
+}
+
+/// What can be wrong with the evaluations and commitments received from other nodes. Functions that can fail in other ways wrap it in `Error::Keygen`
+#[derive(Debug)]
+pub enum KeygenError {
+    /// One evaluation (and one commitment) is needed from every node
+    WrongNumberOfEvaluations { provided: usize, required: usize },
+    /// An evaluation meant for another node was given
+    WrongRecipient { from_node: usize, for_node: usize },
+    /// More than one evaluation or commitment came from the same node
+    DuplicateSender(usize),
+    /// No commitment was given for a node whose evaluation was received
+    MissingCommitment(usize),
+    /// The node's commitment is to a polynomial of the wrong degree
+    InvalidCommitment(usize),
+    /// The node sent an evaluation that doesn't match its commitment, i.e. it cheated
+    InvalidShare(usize),
+    /// The old nodes resharing a key must be distinct nodes, and enough of them to decrypt
+    InvalidOldNodes(String),
+}
+impl fmt::Display for KeygenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeygenError::WrongNumberOfEvaluations { provided, required } => write!(f, "WrongNumberOfEvaluations: {} evaluations provided but {} are required", provided, required),
+            KeygenError::WrongRecipient { from_node, for_node } => write!(f, "WrongRecipient: evaluation from node {} is for node {}", from_node, for_node),
+            KeygenError::DuplicateSender(n) => write!(f, "DuplicateSender: node {} appears more than once", n),
+            KeygenError::MissingCommitment(n) => write!(f, "MissingCommitment: no commitment from node {}", n),
+            KeygenError::InvalidCommitment(n) => write!(f, "InvalidCommitment: commitment from node {} has the wrong degree", n),
+            KeygenError::InvalidShare(n) => write!(f, "InvalidShare: evaluation from node {} does not match its commitment", n),
+            KeygenError::InvalidOldNodes(ref s) => write!(f, "InvalidOldNodes: {}", s),
+        }
+    }
+}
+impl error::Error for KeygenError {}
+
+/// A decryption share along with a proof it was computed correctly: `proof` shows `share` = x*C1 and x*B8 = L_i(0)*A(i)*B8,
+/// where A(i)*B8 is node i's public verification key. The proof is babyjubjub-rs's `DLEQProof`, the same one the OPRF server returns.
+/// Like a `DecryptionShare`, it is checked when it's constructed or deserialized
+#[derive(Clone,Serialize,Deserialize)]
+#[serde(try_from = "UncheckedVerifiableDecryptionShare", into = "UncheckedVerifiableDecryptionShare")]
+pub struct VerifiableDecryptionShare {
+    node_idx: usize,
+    share: Point,
+    proof: DLEQProof
+}
+
+// Serialized form of VerifiableDecryptionShare
+#[derive(Serialize,Deserialize)]
+struct UncheckedVerifiableDecryptionShare {
+    node_idx: usize,
+    share: Point,
+    proof: DLEQProof
+}
+
+impl VerifiableDecryptionShare {
+    /// Checks `node_idx` is a valid node index, `share` is on the curve and in the subgroup, and the proof's points are on the curve.
+    /// Whether the proof holds is checked by `verify`
+    pub fn new(node_idx: usize, share: Point, proof: DLEQProof) -> Result<VerifiableDecryptionShare, Error> {
+        if node_idx == 0 {
+            return Err(Error::InvalidDecryptingNodes("node index must be greater than 0".to_string()));
+        }
+        check_point(&share)?;
+        if !dleq::on_curve(&proof) {
+            return Err(Error::NotOnCurve);
+        }
+        Ok(VerifiableDecryptionShare { node_idx, share, proof })
+    }
+    pub fn node_idx(&self) -> usize {
+        self.node_idx
+    }
+    pub fn share(&self) -> &Point {
+        &self.share
+    }
+    pub fn proof(&self) -> &DLEQProof {
+        &self.proof
+    }
+
+    /// Checks the share was computed from `c1` using the keyshare behind `verification_key`, for decryption by `nodes_to_decrypt_from`
+    pub fn verify(&self, c1: &Point, verification_key: &Point, nodes_to_decrypt_from: &Vec<u32>) -> bool {
+        let basis = lagrange_basis_at_0(self.node_idx as u32, nodes_to_decrypt_from);
+        self.matches(c1, verification_key) &&
+        check_point(&self.share).is_ok() &&
+        self.proof.verify() &&
+        self.proof.xA.equals(verification_key.mul_scalar(&basis.to_bigint()))
+    }
+
+    // Whether the proof is about this share of `c1`, before checking the proof itself
+    fn matches(&self, c1: &Point, verification_key: &Point) -> bool {
+        verification_key.on_curve() &&
+        dleq::on_curve(&self.proof) &&
+        self.proof.A.equals(B8.clone()) &&
+        self.proof.B.equals(c1.clone()) &&
+        self.proof.xB.equals(self.share.clone())
+    }
+
+    /// The share without its proof, for passing to `decrypt`
+    pub fn decryption_share(&self) -> DecryptionShare {
+        DecryptionShare::new_unchecked(self.node_idx, self.share.clone())
+    }
+}
+
+impl TryFrom<UncheckedVerifiableDecryptionShare> for VerifiableDecryptionShare {
+    type Error = Error;
+    fn try_from(s: UncheckedVerifiableDecryptionShare) -> Result<VerifiableDecryptionShare, Error> {
+        VerifiableDecryptionShare::new(s.node_idx, s.share, s.proof)
+    }
+}
+
+impl From<VerifiableDecryptionShare> for UncheckedVerifiableDecryptionShare {
+    fn from(s: VerifiableDecryptionShare) -> UncheckedVerifiableDecryptionShare {
+        UncheckedVerifiableDecryptionShare { node_idx: s.node_idx, share: s.share, proof: s.proof }
+    }
+}
+
+/// A(i)*C1 from node i, not yet multiplied by its Lagrange basis. Unlike the output of `partial_decrypt`, it can be combined with raw shares from any other nodes.
+/// Like a `DecryptionShare`, it is checked when it's constructed or deserialized
+#[derive(Debug,Clone,Serialize,Deserialize)]
+#[serde(try_from = "UncheckedRawDecryptionShare", into = "UncheckedRawDecryptionShare")]
+pub struct RawDecryptionShare {
+    node_idx: usize,
+    share: Point
+}
+
+// Serialized form of RawDecryptionShare
+#[derive(Serialize,Deserialize)]
+struct UncheckedRawDecryptionShare {
+    node_idx: usize,
+    share: Point
+}
+
+impl RawDecryptionShare {
+    /// Checks `node_idx` is a valid node index and `share` is on the curve and in the subgroup
+    pub fn new(node_idx: usize, share: Point) -> Result<RawDecryptionShare, Error> {
+        if node_idx == 0 {
+            return Err(Error::InvalidDecryptingNodes("node index must be greater than 0".to_string()));
+        }
+        check_point(&share)?;
+        Ok(RawDecryptionShare { node_idx, share })
+    }
+    pub(crate) fn new_unchecked(node_idx: usize, share: Point) -> RawDecryptionShare {
+        RawDecryptionShare { node_idx, share }
+    }
+    pub fn node_idx(&self) -> usize {
+        self.node_idx
+    }
+    pub fn share(&self) -> &Point {
+        &self.share
+    }
+}
+
+impl TryFrom<UncheckedRawDecryptionShare> for RawDecryptionShare {
+    type Error = Error;
+    fn try_from(s: UncheckedRawDecryptionShare) -> Result<RawDecryptionShare, Error> {
+        RawDecryptionShare::new(s.node_idx, s.share)
+    }
+}
+
+impl From<RawDecryptionShare> for UncheckedRawDecryptionShare {
+    fn from(s: RawDecryptionShare) -> UncheckedRawDecryptionShare {
+        UncheckedRawDecryptionShare { node_idx: s.node_idx, share: s.share }
+    }
+}
+
+#[derive(Debug)]
+pub enum DecryptionError {
+    /// The number of shares isn't what was expected
+    WrongNumberOfShares { provided: usize, required: usize },
+    /// A share came from a node with no known verification key
+    UnknownNode(usize),
+    /// The node's share failed verification
+    InvalidShare(usize),
+    /// More than one share came from the node
+    DuplicateNode(usize),
+}
+impl fmt::Display for DecryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecryptionError::WrongNumberOfShares { provided, required } => write!(f, "WrongNumberOfShares: {} shares provided but {} are required", provided, required),
+            DecryptionError::UnknownNode(n) => write!(f, "UnknownNode: no verification key for node {}", n),
+            DecryptionError::InvalidShare(n) => write!(f, "InvalidShare: decryption share from node {} failed verification", n),
+            DecryptionError::DuplicateNode(n) => write!(f, "DuplicateNode: more than one share from node {}", n),
+        }
+    }
+}
+impl error::Error for DecryptionError {}
+
+/// Errors from the functions that take input from other parties. Every function that panics on bad input has a `try_` variant returning one of these instead
+#[derive(Debug)]
+pub enum Error {
+    /// Node indices start at 1 and go up to the total number of nodes
+    InvalidNodeIndex { idx: usize, total_nodes: usize },
+    /// At least one node, and no more than the total number of nodes, must be needed to decrypt
+    InvalidThreshold { threshold_nodes: usize, total_nodes: usize },
+    /// Seeds must be 32 bytes
+    InvalidSeed(usize),
+    /// The node's keyshare hasn't been set yet
+    MissingKeyshare,
+    NotOnCurve,
+    NotInSubgroup,
+    /// The nodes given can't decrypt together
+    InvalidDecryptingNodes(String),
+    /// There has to be at least one pubkey share to calculate a public key from
+    NoPubkeyShares,
+    /// Computing a proof failed
+    ProofFailed(String),
+    /// A ciphertext failed authentication: a hybrid ciphertext was tampered with or the decryption shares are wrong, or a TDH2 ciphertext's proof doesn't verify
+    InvalidCiphertext,
+    /// An exponential ElGamal message wasn't in the range searched for it, [0, max]
+    DiscreteLogNotFound { max: u64 },
+    /// Bytes aren't the compact encoding of a valid value
+    InvalidEncoding(String),
+    /// An encrypted keystore couldn't be made or opened, e.g. because the password is wrong
+    Keystore(String),
+    /// A multi-scalar multiplication was given a different number of scalars than points
+    MismatchedLengths { points: usize, scalars: usize },
+    /// The node was made by resharing, so it has no keygen polynomial and no pubkey share
+    ResharedNode,
+    Keygen(KeygenError),
+    Decryption(DecryptionError),
+}
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidNodeIndex { idx, total_nodes } => write!(f, "InvalidNodeIndex: node index {} must be greater than 0 and <= total_nodes {}", idx, total_nodes),
+            Error::InvalidThreshold { threshold_nodes, total_nodes } => write!(f, "InvalidThreshold: threshold_nodes {} must be greater than 0 and <= total_nodes {}", threshold_nodes, total_nodes),
+            Error::InvalidSeed(len) => write!(f, "InvalidSeed: seed must be 32 bytes but is {} bytes", len),
+            Error::MissingKeyshare => write!(f, "MissingKeyshare: keyshare has not been set"),
+            Error::NotOnCurve => write!(f, "NotOnCurve: point is not on the curve"),
+            Error::NotInSubgroup => write!(f, "NotInSubgroup: point is not in the subgroup"),
+            Error::InvalidDecryptingNodes(ref s) => write!(f, "InvalidDecryptingNodes: {}", s),
+            Error::NoPubkeyShares => write!(f, "NoPubkeyShares: no pubkey shares given"),
+            Error::ProofFailed(ref s) => write!(f, "ProofFailed: {}", s),
+            Error::InvalidCiphertext => write!(f, "InvalidCiphertext: ciphertext failed authentication"),
+            Error::DiscreteLogNotFound { max } => write!(f, "DiscreteLogNotFound: message is not between 0 and {}", max),
+            Error::InvalidEncoding(ref s) => write!(f, "InvalidEncoding: {}", s),
+            Error::Keystore(ref s) => write!(f, "Keystore: {}", s),
+            Error::MismatchedLengths { points, scalars } => write!(f, "MismatchedLengths: {} points but {} scalars", points, scalars),
+            Error::ResharedNode => write!(f, "ResharedNode: node was made by resharing and has no pubkey share; use reshared_pubkey for the public key"),
+            Error::Keygen(ref e) => write!(f, "{}", e),
+            Error::Decryption(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+impl error::Error for Error {}
+impl From<KeygenError> for Error {
+    fn from(e: KeygenError) -> Error {
+        Error::Keygen(e)
+    }
+}
+impl From<DecryptionError> for Error {
+    fn from(e: DecryptionError) -> Error {
+        Error::Decryption(e)
+    }
+}
+
+// Nodes only partially decrypt ciphertexts whose proof verifies
+fn check_tdh2(encrypted: &Tdh2Ciphertext) -> Result<(), Error> {
+    if !encrypted.verify() {
+        return Err(Error::InvalidCiphertext);
+    }
+    Ok(())
+}
+
+/// Checks a point from someone else is safe to use, i.e. on the curve and in the subgroup
+pub(crate) fn check_point(p: &Point) -> Result<(), Error> {
+    if !p.on_curve() {
+        return Err(Error::NotOnCurve);
+    }
+    // Note: in_subgroup just checks that order of the point is the order of the subgroup
+    if !p.in_subgroup() {
+        return Err(Error::NotInSubgroup);
+    }
+    Ok(())
+}
+
 impl Node {
     /// Creates a Node using a random keygen polynomial
     /// degree is degree of the polynomial
     pub fn init_rnd(idx: usize, threshold_nodes: usize, total_nodes: usize) -> Node {
-        assert!(idx>0 && idx<=total_nodes, "node index {} must be greater than 0 and <= total_nodes {}", idx, total_nodes);
+        Node::try_init_rnd(idx, threshold_nodes, total_nodes).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_init_rnd(idx: usize, threshold_nodes: usize, total_nodes: usize) -> Result<Node, Error> {
+        Node::check_params(idx, threshold_nodes, total_nodes)?;
         let kp = Polynomial::random_polynomial_fl(threshold_nodes-1);
-        Node::init(idx, kp, total_nodes)
+        Node::try_init(idx, kp, total_nodes)
     }
     /// Creates a Node using a seed for randomness
     /// degree is degree of the polynomial
     pub fn init_from_seed(seed: &Vec<u8>, idx: usize, threshold_nodes: usize, total_nodes: usize) -> Node {
-        assert!(idx>0 && idx<=total_nodes, "node index {} must be greater than 0 and <= total_nodes {}", idx, total_nodes);
+        Node::try_init_from_seed(seed, idx, threshold_nodes, total_nodes).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_init_from_seed(seed: &Vec<u8>, idx: usize, threshold_nodes: usize, total_nodes: usize) -> Result<Node, Error> {
+        Node::check_params(idx, threshold_nodes, total_nodes)?;
+        if seed.len() != 32 {
+            return Err(Error::InvalidSeed(seed.len()));
+        }
         let kp = Polynomial::from_seed(seed, threshold_nodes-1);
-        Node::init(idx, kp, total_nodes)
+        Node::try_init(idx, kp, total_nodes)
+    }
+    fn check_params(idx: usize, threshold_nodes: usize, total_nodes: usize) -> Result<(), Error> {
+        if idx == 0 || idx > total_nodes {
+            return Err(Error::InvalidNodeIndex { idx, total_nodes });
+        }
+        if threshold_nodes == 0 || threshold_nodes > total_nodes {
+            return Err(Error::InvalidThreshold { threshold_nodes, total_nodes });
+        }
+        Ok(())
     }
     /// Creates a Node using a given keygen Polynomial
     pub fn init(idx: usize, polynomial: Polynomial, total_nodes: usize) -> Node {
-        assert!(idx>0, "node index {} must be > 0", idx);
-        let at_zero = polynomial.eval(
-            &BigInt::from_u8(0).unwrap()
-        );
+        Node::try_init(idx, polynomial, total_nodes).unwrap_or_else(|e| panic!("{}", e))
+    }
+    pub fn try_init(idx: usize, polynomial: Polynomial, total_nodes: usize) -> Result<Node, Error> {
+        if idx == 0 {
+            return Err(Error::InvalidNodeIndex { idx, total_nodes });
+        }
+        let at_zero = SecretScalar::new(polynomial.eval_fl(&Fl::zero()));
 
-        Node {
+        Ok(Node {
             idx: idx,
             threshold_nodes: polynomial.deg() + 1,
             total_nodes: total_nodes,
             keygen_polynomial: polynomial,
             keygen_polynomial_at_0: at_zero,
-            keyshare: None
-        }
+            keyshare: None,
+            reshared: false
+        })
     }
     /// num_nodes = how many nodes it needs to share its polynomial evaluations with. Note: all nodes must do this and give result to all other nodes
     pub fn keygen_step1(&self, num_nodes: usize) -> Vec<KeygenHelper> {
@@ -177,6 +582,7 @@ impl Node {
                 let idx = i + 1; // i+1 since nodes are indexed at 1
                 KeygenHelper {
                     for_node: idx, 
+                    from_node: self.idx,
                     value: self.keygen_polynomial.eval(&BigInt::from_usize(idx).unwrap())
                 } 
             }
@@ -185,35 +591,193 @@ impl Node {
         .collect::<Vec<KeygenHelper>>()
     }
 
+    /// Feldman commitments to this node's keygen polynomial. These should be broadcast to all nodes alongside the output of `keygen_step1`
+    pub fn keygen_commitment(&self) -> KeygenCommitment {
+        KeygenCommitment {
+            from_node: self.idx,
+            coefficients: self.keygen_polynomial.commit()
+        }
+    }
+
     /// sets node i's keyshare of as A(i) where A is the secret polynomial. It does this by summing the evaluation of all the other nodes' keygen polynomials at i. 
     /// The other nodes have to send node i their keygen polynomial at i. These other polynomials are other_keygens_for_me
     /// i isn' 0-indexed; it's 1-indexed.
-    pub fn set_keyshare(&mut self, keygen_evals_at_i: &Vec<&KeygenHelper>) {
-        assert!(keygen_evals_at_i.len() == self.total_nodes, "Error setting keyshare: not enough keygen polynomial evaluations at i! One evaluation is needed from *every* node:  {} evaluations provided but {} are required", keygen_evals_at_i.len(), self.total_nodes);
-        let _ = keygen_evals_at_i.iter().for_each(
-            |kh| assert!(kh.for_node == self.idx, "Error setting keyshare: recieved an evaluation of a keygen polynomial at some value other than i")
-        );
-        let keygen_sums_at_i: BigInt = keygen_evals_at_i.iter().map(
-            |kh| &kh.value
-        ).sum();
+    /// Every evaluation is checked against the sender's commitment first, and the keyshare is only set if all of them are valid
+    pub fn set_keyshare(&mut self, keygen_evals_at_i: &Vec<&KeygenHelper>, commitments: &Vec<&KeygenCommitment>) -> Result<(), KeygenError> {
+        let keygen_sums_at_i = self.sum_verified_evals(keygen_evals_at_i, commitments)?;
 
         self.keyshare = Some(
             PrivateKeyShare { share: keygen_sums_at_i }
         );
+        Ok(())
+    }
+
+    /// Checks every evaluation at i against its sender's commitment, then sums them
+    fn sum_verified_evals(&self, evals_at_i: &Vec<&KeygenHelper>, commitments: &Vec<&KeygenCommitment>) -> Result<SecretScalar, KeygenError> {
+        if evals_at_i.len() != self.total_nodes {
+            return Err(KeygenError::WrongNumberOfEvaluations { provided: evals_at_i.len(), required: self.total_nodes });
+        }
+        if commitments.len() != self.total_nodes {
+            return Err(KeygenError::WrongNumberOfEvaluations { provided: commitments.len(), required: self.total_nodes });
+        }
+        Node::verify_evals(self.idx, self.threshold_nodes, evals_at_i, commitments)?;
+
+        Ok(SecretScalar::sum(evals_at_i.iter().map(|kh| &kh.value)))
+    }
+
+    /// Checks every evaluation is for node `idx`, comes from a different node, and matches its sender's commitment to a polynomial with `threshold_nodes` coefficients
+    fn verify_evals(idx: usize, threshold_nodes: usize, evals_at_i: &Vec<&KeygenHelper>, commitments: &Vec<&KeygenCommitment>) -> Result<(), KeygenError> {
+        let mut seen: Vec<usize> = vec![];
+        for kh in evals_at_i.iter() {
+            if kh.for_node != idx {
+                return Err(KeygenError::WrongRecipient { from_node: kh.from_node, for_node: kh.for_node });
+            }
+            if seen.contains(&kh.from_node) {
+                return Err(KeygenError::DuplicateSender(kh.from_node));
+            }
+            seen.push(kh.from_node);
+
+            let mut from_sender = commitments.iter().filter(|c| c.from_node == kh.from_node);
+            let commitment = from_sender.next().ok_or(KeygenError::MissingCommitment(kh.from_node))?;
+            if from_sender.next().is_some() {
+                return Err(KeygenError::DuplicateSender(kh.from_node));
+            }
+            if commitment.coefficients.len() != threshold_nodes {
+                return Err(KeygenError::InvalidCommitment(kh.from_node));
+            }
+            if !commitment.verify(kh) {
+                return Err(KeygenError::InvalidShare(kh.from_node));
+            }
+        }
+        Ok(())
+    }
+
+    /// First step of a proactive share refresh. Makes a random polynomial that is 0 at 0 and returns its evaluations for every node, along with commitments to it.
+    /// Like `keygen_step1`, all nodes must do this and give the results to all other nodes
+    pub fn refresh_step1(&self) -> (Vec<KeygenHelper>, KeygenCommitment) {
+        let refresher = Node::init(self.idx, Polynomial::random_polynomial_fl_zero_at_0(self.threshold_nodes-1), self.total_nodes);
+        (refresher.keygen_step1(self.total_nodes), refresher.keygen_commitment())
+    }
+
+    /// Adds every node's refresh polynomial evaluated at i to this node's keyshare. Since all the refresh polynomials are 0 at 0, the new keyshares
+    /// are points on a new secret polynomial with the same value at 0, so the shared public key stays the same but old keyshares can no longer be combined with new ones.
+    /// The old keyshare is overwritten; it should be deleted anywhere else it is stored.
+    /// Note the verification keys change, so they have to be computed with the refresh commitments as well as the keygen commitments
+    pub fn refresh_keyshare(&mut self, refresh_evals_at_i: &Vec<&KeygenHelper>, commitments: &Vec<&KeygenCommitment>) -> Result<(), Error> {
+        let old_share = self.keyshare.as_ref().ok_or(Error::MissingKeyshare)?;
+        // a refresh polynomial that isn't 0 at 0 would change the secret key
+        let zero = Point { x: Fr::zero(), y: Fr::one() };
+        if let Some(c) = commitments.iter().find(|c| c.pubkey_share().map_or(false, |p| !p.equals(zero.clone()))) {
+            return Err(KeygenError::InvalidCommitment(c.from_node).into());
+        }
+        let refresh_sums_at_i = self.sum_verified_evals(refresh_evals_at_i, commitments)?;
+
+        let mut new_share = *old_share.share.expose_secret();
+        new_share.add_assign(refresh_sums_at_i.expose_secret());
+        self.keyshare = Some(
+            PrivateKeyShare { share: SecretScalar::new(new_share) }
+        );
+        secret::wipe(&mut new_share);
+        Ok(())
+    }
+
+    /// First step of resharing the key to a new committee of `new_total_nodes` nodes, `new_threshold_nodes` of which will be needed to decrypt.
+    /// This node sub-shares its keyshare A(i) with a random polynomial g_i where g_i(0) = A(i). Returns g_i's evaluations for every new node, along with commitments to it.
+    /// The old nodes taking part must agree on who they are beforehand, and there must be at least `threshold_nodes` of them
+    pub fn reshare_step1(&self, new_threshold_nodes: usize, new_total_nodes: usize) -> Result<(Vec<KeygenHelper>, KeygenCommitment), Error> {
+        if new_threshold_nodes == 0 || new_threshold_nodes > new_total_nodes {
+            return Err(Error::InvalidThreshold { threshold_nodes: new_threshold_nodes, total_nodes: new_total_nodes });
+        }
+        let keyshare = self.keyshare.as_ref().ok_or(Error::MissingKeyshare)?;
+        let subsharer = Node::init(
+            self.idx,
+            Polynomial::random_polynomial_fl_with_constant(new_threshold_nodes-1, keyshare.share.expose_secret()),
+            new_total_nodes
+        );
+        Ok((subsharer.keygen_step1(new_total_nodes), subsharer.keygen_commitment()))
+    }
+
+    /// Creates node `idx` of a new committee from the sub-shares sent to it by the old nodes `old_nodes`. Its keyshare is ∑L_i(0)*g_i(idx) for i in `old_nodes`,
+    /// which is a point on a new secret polynomial with the same value at 0, so the new committee has the same public key as the old one.
+    /// `old_nodes` must be at least `old_threshold_nodes` distinct nodes of the old committee.
+    /// Each sub-share is checked against its sender's commitment, and each commitment's constant term must be the sender's verification key `old_verification_keys[i-1]`.
+    /// Nodes made this way didn't take part in key generation, so they have no pubkey share (`try_pubkey_share` returns an error); use `reshared_pubkey` instead
+    pub fn init_from_reshare(idx: usize, threshold_nodes: usize, total_nodes: usize, old_threshold_nodes: usize, old_nodes: &Vec<u32>, reshare_evals_at_i: &Vec<&KeygenHelper>, commitments: &Vec<&KeygenCommitment>, old_verification_keys: &Vec<Point>) -> Result<Node, Error> {
+        Node::check_params(idx, threshold_nodes, total_nodes)?;
+        check_old_nodes(old_nodes, old_threshold_nodes)?;
+        if reshare_evals_at_i.len() != old_nodes.len() {
+            return Err(KeygenError::WrongNumberOfEvaluations { provided: reshare_evals_at_i.len(), required: old_nodes.len() }.into());
+        }
+        if let Some(kh) = reshare_evals_at_i.iter().find(|kh| !old_nodes.contains(&(kh.from_node as u32))) {
+            return Err(KeygenError::WrongRecipient { from_node: kh.from_node, for_node: kh.for_node }.into());
+        }
+        // the sub-sharing polynomials must be for the old keyshares, not some other value
+        for kh in reshare_evals_at_i.iter() {
+            let commitment = commitments.iter().find(|c| c.from_node == kh.from_node).ok_or(KeygenError::MissingCommitment(kh.from_node))?;
+            let vk = kh.from_node.checked_sub(1).and_then(|i| old_verification_keys.get(i)).ok_or(KeygenError::MissingCommitment(kh.from_node))?;
+            if !commitment.pubkey_share().map_or(false, |p| p.equals(vk.clone())) {
+                return Err(KeygenError::InvalidCommitment(kh.from_node).into());
+            }
+        }
+        Node::verify_evals(idx, threshold_nodes, reshare_evals_at_i, commitments)?;
+
+        let mut share = Fl::zero();
+        reshare_evals_at_i.iter().for_each(|kh| {
+            let mut term = lagrange_basis_at_0(kh.from_node as u32, old_nodes);
+            term.mul_assign(&Fl::from_bigint(&kh.value));
+            share.add_assign(&term);
+        });
+
+        let mut node = Node::init(idx, Polynomial::from_coeffs(vec![BigInt::from_u8(0).unwrap(); threshold_nodes]), total_nodes);
+        node.keyshare = Some(PrivateKeyShare { share: SecretScalar::new(share) });
+        node.reshared = true;
+        secret::wipe(&mut share);
+        Ok(node)
     }
 
-    pub fn pubkey_share(&self) -> Point {
-        B8.mul_scalar(&self.keygen_polynomial_at_0)
+    /// This node's keygen polynomial at 0 times B8. The pubkey shares of all the nodes sum to the shared public key. Panics if the node was made by resharing
+    pub fn pubkey_share(&self) -> PubkeyShare {
+        self.try_pubkey_share().unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_pubkey_share(&self) -> Result<PubkeyShare, Error> {
+        if self.reshared {
+            return Err(Error::ResharedNode);
+        }
+        Ok(PubkeyShare::new_unchecked(self.keygen_polynomial_at_0.mul_b8()))
     }
     
     // NOTE: look more into security of a user being able to ask "decrypt this with nodes i1, i2, and i3", then being able to asl "decrypt this with nodes i4, i5, and i6". Does this reveal any information about the private key? I would assume not because this is standard, but seems strange and still worth more detailed analysis.
 
-    /// Return this node's secret share * this node's Lagrange basis, evaluated at 0. All nodes' secret_lagrange_basis_at_0() should sum to the shared private key
-    fn secret_lagrange_basis_at_0(&self, node_indices: &Vec<u32>) -> Fl {
-        assert!(node_indices.len() == self.total_nodes, "Error: node_indices must be the same length as total_nodes");
+    /// Checks `node_indices` is a set of nodes that can decrypt together and that this node is part of it
+    fn check_decrypting_nodes(&self, node_indices: &Vec<u32>) -> Result<(), Error> {
+        if node_indices.len() < self.threshold_nodes {
+            return Err(Error::InvalidDecryptingNodes(format!("{} nodes to decrypt from but at least {} are needed", node_indices.len(), self.threshold_nodes)));
+        }
+        for (n, i) in node_indices.iter().enumerate() {
+            if *i == 0 || (*i as usize) > self.total_nodes {
+                return Err(Error::InvalidDecryptingNodes(format!("node index {} must be greater than 0 and <= total_nodes {}", i, self.total_nodes)));
+            }
+            if node_indices[..n].contains(i) {
+                return Err(Error::InvalidDecryptingNodes(format!("node index {} appears more than once in the nodes to decrypt from", i)));
+            }
+        }
+        if !node_indices.contains(&(self.idx as u32)) {
+            return Err(Error::InvalidDecryptingNodes(format!("node {} is not one of the nodes to decrypt from", self.idx)));
+        }
+        Ok(())
+    }
+
+    fn keyshare(&self) -> Result<&SecretScalar, Error> {
+        self.keyshare.as_ref().map(|k| &k.share).ok_or(Error::MissingKeyshare)
+    }
+
+    /// Return this node's secret share * this node's Lagrange basis, evaluated at 0. All `node_indices` nodes' secret_lagrange_basis_at_0() should sum to the shared private key.
+    /// Any set of at least `threshold_nodes` distinct nodes works
+    fn secret_lagrange_basis_at_0(&self, node_indices: &Vec<u32>) -> Result<Fl, Error> {
+        self.check_decrypting_nodes(node_indices)?;
         let mut basis = lagrange_basis_at_0(self.idx as u32, &node_indices);
-        basis.mul_assign(&Fl::from_bigint(&self.keyshare.as_ref().unwrap().share));
-        basis
+        basis.mul_assign(self.keyshare()?.expose_secret());
+        Ok(basis)
     }
 
     // /// Performs a partial decryption on C1 of the ElGamal encrypted value (C1, C2). Returns secret share * C1
@@ -222,45 +786,288 @@ impl Node {
     //     .partial_decrypt(c1)
     // }
 
-    /// Performs a partial decryption on C1 of the ElGamal encrypted value (C1, C2). Returns secret share * my lagrange basis * C1
-    pub fn partial_decrypt(&self, c1: &Point, nodes_to_decrypt_from: &Vec<u32>) -> Point {
-        assert!(c1.on_curve(), "Error: C1 is not on the curve!");
-        assert!(c1.in_subgroup(), "Error: C1 is not in the subgroup!");
-        c1.mul_scalar(&self.secret_lagrange_basis_at_0(nodes_to_decrypt_from).to_bigint())
+    /// Performs a partial decryption of a TDH2 ciphertext. Returns secret share * my lagrange basis * C1. Panics if the ciphertext's proof doesn't verify
+    pub fn partial_decrypt(&self, encrypted: &Tdh2Ciphertext, nodes_to_decrypt_from: &Vec<u32>) -> DecryptionShare {
+        self.try_partial_decrypt(encrypted, nodes_to_decrypt_from).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_partial_decrypt(&self, encrypted: &Tdh2Ciphertext, nodes_to_decrypt_from: &Vec<u32>) -> Result<DecryptionShare, Error> {
+        check_tdh2(encrypted)?;
+        self.try_partial_decrypt_c1(&encrypted.c1, nodes_to_decrypt_from)
     }
 
-    
+    /// Performs a partial decryption of a TDH2 ciphertext without applying a Lagrange basis. Returns secret share * C1, which `decrypt_raw` can combine with the raw shares of any other nodes.
+    /// Panics if the ciphertext's proof doesn't verify
+    pub fn raw_partial_decrypt(&self, encrypted: &Tdh2Ciphertext) -> RawDecryptionShare {
+        self.try_raw_partial_decrypt(encrypted).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_raw_partial_decrypt(&self, encrypted: &Tdh2Ciphertext) -> Result<RawDecryptionShare, Error> {
+        check_tdh2(encrypted)?;
+        self.try_raw_partial_decrypt_c1(&encrypted.c1)
+    }
+
+    /// Same as `partial_decrypt` but also gives a Chaum-Pedersen proof the share was computed with this node's keyshare
+    pub fn partial_decrypt_with_proof(&self, encrypted: &Tdh2Ciphertext, nodes_to_decrypt_from: &Vec<u32>) -> VerifiableDecryptionShare {
+        self.try_partial_decrypt_with_proof(encrypted, nodes_to_decrypt_from).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_partial_decrypt_with_proof(&self, encrypted: &Tdh2Ciphertext, nodes_to_decrypt_from: &Vec<u32>) -> Result<VerifiableDecryptionShare, Error> {
+        check_tdh2(encrypted)?;
+        self.try_partial_decrypt_c1_with_proof(&encrypted.c1, nodes_to_decrypt_from)
+    }
+
+    /* Partial decryption of a bare C1, for plain, hybrid, multi-recipient, exponential and rerandomized ciphertexts, which have no proof that the encrypter knows the nonce.
+    Nothing stops a caller from passing a C1 chosen by an attacker, and decrypting one can leak the message of any ciphertext with that C1 (see the README).
+    Only call these for ciphertexts the caller has its own reason to trust, e.g. ones it stored itself, and use the TDH2 versions above for anything coming from outside */
+
+    /// Performs a partial decryption of the C1 of any ciphertext. Returns secret share * my lagrange basis * C1. Not chosen-ciphertext safe, unlike `partial_decrypt`
+    pub fn partial_decrypt_c1(&self, c1: &Point, nodes_to_decrypt_from: &Vec<u32>) -> DecryptionShare {
+        self.try_partial_decrypt_c1(c1, nodes_to_decrypt_from).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_partial_decrypt_c1(&self, c1: &Point, nodes_to_decrypt_from: &Vec<u32>) -> Result<DecryptionShare, Error> {
+        check_point(c1)?;
+        let mut basis = self.secret_lagrange_basis_at_0(nodes_to_decrypt_from)?;
+        let share = mul_scalar_ct(c1, &basis);
+        secret::wipe(&mut basis);
+        Ok(DecryptionShare::new_unchecked(self.idx, share))
+    }
+
+    /// Same as `raw_partial_decrypt` for the C1 of any ciphertext. Returns secret share * C1. Not chosen-ciphertext safe
+    pub fn raw_partial_decrypt_c1(&self, c1: &Point) -> RawDecryptionShare {
+        self.try_raw_partial_decrypt_c1(c1).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_raw_partial_decrypt_c1(&self, c1: &Point) -> Result<RawDecryptionShare, Error> {
+        check_point(c1)?;
+        Ok(RawDecryptionShare::new_unchecked(self.idx, self.keyshare()?.mul_point(c1)))
+    }
+
+    /// Same as `partial_decrypt_with_proof` for the C1 of any ciphertext. Not chosen-ciphertext safe
+    pub fn partial_decrypt_c1_with_proof(&self, c1: &Point, nodes_to_decrypt_from: &Vec<u32>) -> VerifiableDecryptionShare {
+        self.try_partial_decrypt_c1_with_proof(c1, nodes_to_decrypt_from).unwrap_or_else(|e| panic!("Error: {}", e))
+    }
+    pub fn try_partial_decrypt_c1_with_proof(&self, c1: &Point, nodes_to_decrypt_from: &Vec<u32>) -> Result<VerifiableDecryptionShare, Error> {
+        check_point(c1)?;
+        let mut basis = self.secret_lagrange_basis_at_0(nodes_to_decrypt_from)?;
+        let proof = DLEQProof::new(basis, B8.clone(), c1.clone());
+        secret::wipe(&mut basis);
+        let proof = proof.map_err(|e| Error::ProofFailed(e.to_string()))?;
+        Ok(VerifiableDecryptionShare {
+            node_idx: self.idx,
+            share: proof.xB.clone(),
+            proof: proof
+        })
+    }
+
+    /// A(i)*B8, which anyone can use to verify this node's decryption shares. Can also be computed without the node's help using `verification_key`
+    pub fn verification_key(&self) -> Result<Point, Error> {
+        Ok(self.keyshare()?.mul_b8())
+    }
+
+    /// This node with its secrets in the clear, for serializing it. Prefer `keystore::encrypt_node`, which uses this to encrypt the node
+    pub fn exposed(&self) -> ExposedNode<'_> {
+        ExposedNode {
+            idx: self.idx,
+            threshold_nodes: self.threshold_nodes,
+            total_nodes: self.total_nodes,
+            keygen_polynomial: &self.keygen_polynomial,
+            keygen_polynomial_at_0: &self.keygen_polynomial_at_0,
+            keyshare: self.keyshare.as_ref().map(|k| ExposedKeyshare { share: &k.share }),
+            reshared: self.reshared
+        }
+    }
 
 }
 
 
 
 /* Functions to help encrypt to nodes and decrypt from nodes. Adds their pubkeys shares */
-pub fn calculate_pubkey(pubkey_shares: Vec<Point>) -> Option<Point> {
-    let mut acc: Option<Point> = None;
-    for point in pubkey_shares.iter() {
-        match acc {
-            None => acc = Some(point.clone()),
-            Some(_) => acc = Some(acc.unwrap().add(point))
-        }
+pub fn calculate_pubkey(pubkey_shares: Vec<PubkeyShare>) -> Option<PublicKey> {
+    if pubkey_shares.is_empty() {
+        return None;
     }
-    acc
+    let points: Vec<Point> = pubkey_shares.into_iter().map(PubkeyShare::into_point).collect();
+    Some(PublicKey::new_unchecked(sum_points(&points)))
+}
+
+/// Same as `calculate_pubkey` but returns an error instead of `None` when there are no pubkey shares
+pub fn try_calculate_pubkey(pubkey_shares: Vec<PubkeyShare>) -> Result<PublicKey, Error> {
+    calculate_pubkey(pubkey_shares).ok_or(Error::NoPubkeyShares)
 }
 
 // Reconstructs the Diffie-Hellman shared secret using decryption shares
-pub fn reconstruct_dh_secret(decryption_shares: Vec<Point>) -> Point {
-    decryption_shares.iter().map(|x|x.clone()).reduce(
-        |a,b| a.add(&b)
-    ).unwrap()
+pub fn reconstruct_dh_secret(decryption_shares: Vec<DecryptionShare>) -> Point {
+    let points: Vec<Point> = decryption_shares.into_iter().map(|s| s.point().clone()).collect();
+    sum_points(&points)
+}
+
+/// Same as `reconstruct_dh_secret`, after checking there are `num_shares_needed` shares, each from a different node
+pub fn try_reconstruct_dh_secret(decryption_shares: Vec<DecryptionShare>, num_shares_needed: u64) -> Result<Point, Error> {
+    if decryption_shares.is_empty() || decryption_shares.len().to_u64().unwrap() != num_shares_needed {
+        return Err(Error::Decryption(DecryptionError::WrongNumberOfShares { provided: decryption_shares.len(), required: num_shares_needed as usize }));
+    }
+    for (n, share) in decryption_shares.iter().enumerate() {
+        if decryption_shares[..n].iter().any(|s| s.node_idx() == share.node_idx()) {
+            return Err(Error::InvalidDecryptingNodes(format!("more than one share from node {}", share.node_idx())));
+        }
+    }
+    Ok(reconstruct_dh_secret(decryption_shares))
+}
+
+/// The public key of a committee made by resharing, computed from the old nodes' resharing commitments as ∑L_i(0)*A(i)*B8. It should equal the old committee's public key.
+/// `old_nodes` must be at least `old_threshold_nodes` distinct nodes, each with a commitment in `commitments`
+pub fn reshared_pubkey(commitments: &Vec<&KeygenCommitment>, old_nodes: &Vec<u32>, old_threshold_nodes: usize) -> Result<PublicKey, Error> {
+    check_old_nodes(old_nodes, old_threshold_nodes)?;
+    let mut points = Vec::with_capacity(old_nodes.len());
+    let mut scalars = Vec::with_capacity(old_nodes.len());
+    for i in old_nodes {
+        let c = commitments.iter().find(|c| c.from_node == *i as usize).ok_or(KeygenError::MissingCommitment(*i as usize))?;
+        points.push(c.pubkey_share().ok_or(KeygenError::InvalidCommitment(*i as usize))?);
+        scalars.push(lagrange_basis_at_0(*i, old_nodes).to_bigint());
+    }
+    PublicKey::new(multi_scalar_mul(&points, &scalars)?)
+}
+
+// The old nodes resharing a key must be distinct nodes, and at least as many as are needed to decrypt, or their sub-shares don't add up to the key
+fn check_old_nodes(old_nodes: &Vec<u32>, old_threshold_nodes: usize) -> Result<(), KeygenError> {
+    if old_threshold_nodes == 0 || old_nodes.len() < old_threshold_nodes {
+        return Err(KeygenError::InvalidOldNodes(format!("{} old nodes but at least {} are needed", old_nodes.len(), old_threshold_nodes.max(1))));
+    }
+    for (n, i) in old_nodes.iter().enumerate() {
+        if *i == 0 {
+            return Err(KeygenError::InvalidOldNodes("node index 0 doesn't exist".to_string()));
+        }
+        if old_nodes[..n].contains(i) {
+            return Err(KeygenError::InvalidOldNodes(format!("node {} appears more than once", i)));
+        }
+    }
+    Ok(())
+}
+
+/// Computes node i's verification key A(i)*B8 from the Feldman commitments of every node, as ∑ A_j(i)*B8
+pub fn verification_key(commitments: &Vec<KeygenCommitment>, node_idx: usize) -> Point {
+    let evals: Vec<Point> = commitments.iter().map(|c| c.eval(node_idx)).collect();
+    sum_points(&evals)
 }
 
-pub fn decrypt(encrypted: ElGamalEncryption, shares: Vec<Point>, num_shares_needed: u64) -> Point {
-    assert!(shares.len().to_u64().unwrap() == num_shares_needed);
+/// Decrypts after checking every share's proof. `verification_keys[i]` is node i+1's verification key.
+/// The proofs are checked together as one random linear combination (see `dleq`). If a share is bad, the error names the node that sent it
+pub fn decrypt_verified(encrypted: Ciphertext, shares: Vec<VerifiableDecryptionShare>, verification_keys: &Vec<Point>, num_shares_needed: u64) -> Result<Point, DecryptionError> {
+    if shares.is_empty() || shares.len().to_u64().unwrap() != num_shares_needed {
+        return Err(DecryptionError::WrongNumberOfShares { provided: shares.len(), required: num_shares_needed as usize });
+    }
+    // with a node counted twice, the Lagrange basis is wrong but every proof still verifies
+    if let Some((_, s)) = shares.iter().enumerate().find(|(n, s)| shares[..*n].iter().any(|t| t.node_idx == s.node_idx)) {
+        return Err(DecryptionError::DuplicateNode(s.node_idx));
+    }
+    let nodes_to_decrypt_from: Vec<u32> = shares.iter().map(|s| s.node_idx as u32).collect();
+    let verification_keys: Vec<&Point> = shares.iter().map(
+        |s| s.node_idx.checked_sub(1).and_then(|i| verification_keys.get(i)).ok_or(DecryptionError::UnknownNode(s.node_idx))
+    ).collect::<Result<_, _>>()?;
+
+    // every proof, and that each proof's xA is L_i(0) times the node's verification key
+    let mut batch = BatchEquation::new();
+    for (s, vk) in shares.iter().zip(verification_keys.iter()) {
+        if !s.matches(encrypted.c1(), vk) {
+            return Err(DecryptionError::InvalidShare(s.node_idx));
+        }
+        batch.add_proof(&s.proof);
+        let basis = lagrange_basis_at_0(s.node_idx as u32, &nodes_to_decrypt_from);
+        batch.add(vec![(s.proof.xA.clone(), BigInt::from(1u8)), ((*vk).clone(), -basis.to_bigint())]);
+    }
+    // the proofs only hold up to small-order components, so the shares must also be in the subgroup. If each is, so is their sum
+    let sum = sum_points(&shares.iter().map(|s| s.share.clone()).collect::<Vec<Point>>());
+    if !batch.holds() || !sum.in_subgroup() {
+        if let Some((s, _)) = shares.iter().zip(verification_keys.iter()).find(|(s, vk)| !s.verify(encrypted.c1(), vk, &nodes_to_decrypt_from)) {
+            return Err(DecryptionError::InvalidShare(s.node_idx));
+        }
+    }
 
-    let reconstructed_dh_secret = reconstruct_dh_secret(shares);
+    // the same as `try_decrypt`, reusing the sum of the shares, which are now known to be valid and from different nodes
+    Ok(encrypted.c2().add(&sum.neg()))
+}
+
+/// Reconstructs the Diffie-Hellman shared secret from raw decryption shares as ∑L_i(0)*A(i)*C1, using the nodes the shares came from as the decrypting set
+pub fn combine_raw_shares(shares: &[RawDecryptionShare]) -> Point {
+    try_combine_raw_shares(shares).unwrap_or_else(|e| panic!("Error: {}", e))
+}
+pub fn try_combine_raw_shares(shares: &[RawDecryptionShare]) -> Result<Point, Error> {
+    if shares.is_empty() {
+        return Err(Error::Decryption(DecryptionError::WrongNumberOfShares { provided: 0, required: 1 }));
+    }
+    let node_indices: Vec<u32> = shares.iter().map(|s| s.node_idx as u32).collect();
+    for (n, s) in shares.iter().enumerate() {
+        if s.node_idx == 0 {
+            return Err(Error::InvalidDecryptingNodes("node index must be greater than 0".to_string()));
+        }
+        if node_indices[..n].contains(&node_indices[n]) {
+            return Err(Error::InvalidDecryptingNodes(format!("more than one share from node {}", s.node_idx)));
+        }
+        check_point(&s.share)?;
+    }
+    let points: Vec<Point> = shares.iter().map(|s| s.share.clone()).collect();
+    let scalars: Vec<BigInt> = node_indices.iter().map(|i| lagrange_basis_at_0(*i, &node_indices).to_bigint()).collect();
+    multi_scalar_mul(&points, &scalars)
+}
+
+/// Decrypts using the first `threshold_nodes` raw shares. The shares can be from whichever nodes answered first; if decryption doesn't give what's expected,
+/// it can be retried with the same shares in a different order instead of asking the nodes again
+pub fn decrypt_raw(encrypted: Ciphertext, shares: &Vec<RawDecryptionShare>, threshold_nodes: usize) -> Point {
+    try_decrypt_raw(encrypted, shares, threshold_nodes).unwrap_or_else(|e| panic!("Error: {}", e))
+}
+pub fn try_decrypt_raw(encrypted: Ciphertext, shares: &Vec<RawDecryptionShare>, threshold_nodes: usize) -> Result<Point, Error> {
+    if shares.len() < threshold_nodes {
+        return Err(Error::Decryption(DecryptionError::WrongNumberOfShares { provided: shares.len(), required: threshold_nodes }));
+    }
+    let reconstructed_dh_secret = try_combine_raw_shares(&shares[..threshold_nodes])?;
+    Ok(encrypted.c2().add(&reconstructed_dh_secret.neg()))
+}
+
+pub fn decrypt(encrypted: Ciphertext, shares: Vec<DecryptionShare>, num_shares_needed: u64) -> Point {
+    try_decrypt(encrypted, shares, num_shares_needed).unwrap_or_else(|e| panic!("Error: {}", e))
+}
+pub fn try_decrypt(encrypted: Ciphertext, shares: Vec<DecryptionShare>, num_shares_needed: u64) -> Result<Point, Error> {
+    let reconstructed_dh_secret = try_reconstruct_dh_secret(shares, num_shares_needed)?;
 
     // The Diffie-Hellman "shared secret" in ElGamal system coincides with reconstructed "shared secret" at y-intercept, even though these are shared in different ways!
-    encrypted.c2.add(&reconstructed_dh_secret.neg())
+    Ok(encrypted.c2().add(&reconstructed_dh_secret.neg()))
+}
+
+/// Proof that a ciphertext is a re-randomization of another: `proof` shows C1' - C1 = s*B8 and C2' - C2 = s*PK for the same s
+#[derive(Serialize,Deserialize)]
+pub struct RerandomizationProof {
+    pub proof: DLEQProof
+}
+
+impl RerandomizationProof {
+    /// Checks `rerandomized` encrypts the same message to `pubkey` as `original`
+    pub fn verify(&self, pubkey: &PublicKey, original: &Ciphertext, rerandomized: &Ciphertext) -> bool {
+        self.proof.verify() &&
+        self.proof.A.equals(B8.clone()) &&
+        self.proof.B.equals((**pubkey).clone()) &&
+        self.proof.xA.equals(rerandomized.c1().add(&original.c1().neg())) &&
+        self.proof.xB.equals(rerandomized.c2().add(&original.c2().neg()))
+    }
+}
+
+/// Makes a new encryption of the same message to `pubkey` that can't be linked to `encrypted` without the private key, by adding an encryption of the identity to it
+pub fn rerandomize(pubkey: &PublicKey, encrypted: &Ciphertext) -> Ciphertext {
+    let nonce = rand::thread_rng().gen_bigint_range(&BigInt::from(1u8), &SUBORDER);
+    rerandomize_with_nonce(pubkey, encrypted, &nonce)
+}
+
+/// Same as `rerandomize` but with a given nonce, which must be secret and random
+pub fn rerandomize_with_nonce(pubkey: &PublicKey, encrypted: &Ciphertext, nonce: &BigInt) -> Ciphertext {
+    let nonce = to_fl(nonce);
+    // adding points in the subgroup keeps C1 in it and C2 on the curve
+    Ciphertext::new_unchecked(
+        encrypted.c1().add(&mul_b8_ct(&nonce)),
+        encrypted.c2().add(&mul_scalar_ct(pubkey, &nonce))
+    )
+}
+
+/// Same as `rerandomize` but also proves the result is a re-randomization of `encrypted`, without revealing the nonce
+pub fn rerandomize_with_proof(pubkey: &PublicKey, encrypted: &Ciphertext) -> Result<(Ciphertext, RerandomizationProof), Error> {
+    let nonce = rand::thread_rng().gen_bigint_range(&BigInt::from(1u8), &SUBORDER);
+    let proof = DLEQProof::new(to_fl(&nonce), B8.clone(), (**pubkey).clone()).map_err(|e| Error::ProofFailed(e.to_string()))?;
+    Ok((rerandomize_with_nonce(pubkey, encrypted, &nonce), RerandomizationProof { proof }))
 }
 
 
@@ -268,7 +1075,6 @@ pub fn decrypt(encrypted: ElGamalEncryption, shares: Vec<Point>, num_shares_need
 mod tests {
     use std::{vec};
 
-    use babyjubjub_rs::{encrypt_elgamal};
     use num_bigint::ToBigInt;
 
     use super::*;
@@ -289,6 +1095,53 @@ mod tests {
     }
 
 
+    // Helper function to initialize some nodes and do the whole keygen process, for any threshold and number of nodes
+    pub(crate) fn init_keyshared_nodes(threshold_nodes: usize, total_nodes: usize) -> Vec<Node> {
+        let mut nodes: Vec<Node> = (1..total_nodes+1).map(|i| Node::init_rnd(i, threshold_nodes, total_nodes)).collect();
+        let keygen_helpers: Vec<Vec<KeygenHelper>> = nodes.iter().map(|node| node.keygen_step1(total_nodes)).collect();
+        let commitments: Vec<KeygenCommitment> = nodes.iter().map(|node| node.keygen_commitment()).collect();
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
+        nodes.iter_mut().enumerate().for_each(|(i, node)| {
+            let for_me: Vec<&KeygenHelper> = keygen_helpers.iter().map(|outputs| &outputs[i]).collect();
+            node.set_keyshare(&for_me, &commitments).unwrap();
+        });
+        nodes
+    }
+
+    // Helpers stored before from_node existed should still set the same keyshare, taking their senders from their positions
+    #[test]
+    fn test_legacy_keygen_helpers() {
+        let mut nodes: Vec<Node> = (1..4).map(|i| Node::init_rnd(i, 2, 3)).collect();
+        let keygen_helpers: Vec<Vec<KeygenHelper>> = nodes.iter().map(|node| node.keygen_step1(3)).collect();
+        let commitments: Vec<KeygenCommitment> = nodes.iter().map(|node| node.keygen_commitment()).collect();
+        let for_node_2: Vec<&KeygenHelper> = keygen_helpers.iter().map(|outputs| &outputs[1]).collect();
+
+        let mut legacy = serde_json::to_value(&for_node_2).unwrap();
+        legacy.as_array_mut().unwrap().iter_mut().for_each(|h| { h.as_object_mut().unwrap().remove("from_node"); });
+        let parsed = keygen_helpers_from_json(&legacy.to_string()).unwrap();
+        assert_eq!(parsed.iter().map(|h| h.from_node).collect::<Vec<usize>>(), vec![1, 2, 3]);
+        nodes[1].set_keyshare(&parsed.iter().collect(), &commitments.iter().collect()).unwrap();
+
+        // current helpers keep their senders, and a helper with no sender is rejected rather than guessed at
+        let current = keygen_helpers_from_json(&serde_json::to_string(&vec![&keygen_helpers[2][0], &keygen_helpers[0][0]]).unwrap()).unwrap();
+        assert_eq!((current[0].from_node, current[1].from_node), (3, 1));
+        let unfilled: Vec<KeygenHelper> = serde_json::from_value(legacy).unwrap();
+        assert!(matches!(nodes[1].set_keyshare(&unfilled.iter().collect(), &commitments.iter().collect()), Err(KeygenError::MissingCommitment(0))));
+    }
+
+    // All subsets of `indices` with exactly `size` elements
+    fn subsets(indices: &[u32], size: usize) -> Vec<Vec<u32>> {
+        if size == 0 {
+            return vec![vec![]];
+        }
+        if indices.len() < size {
+            return vec![];
+        }
+        let mut with_first: Vec<Vec<u32>> = subsets(&indices[1..], size - 1).into_iter().map(|mut s| { s.insert(0, indices[0]); s }).collect();
+        with_first.extend(subsets(&indices[1..], size));
+        with_first
+    }
+
     #[test]
     fn test_pubkey() {
         let node1 = Node::init_rnd(1,2, 2);
@@ -298,7 +1151,7 @@ mod tests {
             vec![node1.pubkey_share(), node2.pubkey_share()]
         ).unwrap();
         // since this test can access private variables, lets see whether the pubkey is correct:
-        let secret_key_nobody_knows = node1.keygen_polynomial_at_0 + node2.keygen_polynomial_at_0;
+        let secret_key_nobody_knows = node1.keygen_polynomial_at_0.expose_bigint() + node2.keygen_polynomial_at_0.expose_bigint();
         
         assert!(shared_pubkey.equals(B8.mul_scalar(&secret_key_nobody_knows)));
         // node1.pubkey_share(num_nodes)
@@ -334,15 +1187,18 @@ mod tests {
             |outputs| &outputs[2]
         ).collect();
 
-        nodes[0].set_keyshare(&node1_inputs); 
-        nodes[1].set_keyshare(&node2_inputs); 
-        nodes[2].set_keyshare(&node3_inputs); 
+        let commitments: Vec<KeygenCommitment> = nodes.iter().map(|node| node.keygen_commitment()).collect();
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
+
+        nodes[0].set_keyshare(&node1_inputs, &commitments).unwrap(); 
+        nodes[1].set_keyshare(&node2_inputs, &commitments).unwrap(); 
+        nodes[2].set_keyshare(&node3_inputs, &commitments).unwrap(); 
 
         nodes.iter().for_each(
             |n| 
             assert_eq!(
                 secret_polynomial_nobody_knows.eval(&n.idx.to_bigint().unwrap()),
-                n.keyshare.as_ref().unwrap().share
+                n.keyshare.as_ref().unwrap().share.expose_bigint()
             )
         )
 
@@ -359,10 +1215,13 @@ mod tests {
         let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
         let to_node2 = vec![&from_node1[1], &from_node2[1], &from_node3[1]];
         let to_node3 = vec![&from_node1[2], &from_node2[2], &from_node3[2]];
+        // every node also broadcasts commitments to its keygen polynomial so the evaluations can be checked
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
         // and finally each node reconstructs their part of the secret
-        node1.set_keyshare(&to_node1);
-        node2.set_keyshare(&to_node2);
-        node3.set_keyshare(&to_node3);
+        node1.set_keyshare(&to_node1, &commitments).unwrap();
+        node2.set_keyshare(&to_node2, &commitments).unwrap();
+        node3.set_keyshare(&to_node3, &commitments).unwrap();
 
 
         // some arbitrary nonce and public version
@@ -370,18 +1229,18 @@ mod tests {
         let public_nonce = B8.mul_scalar(nonce);
 
         // Try decrypting
-        let d1 = node1.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
-        let d2 = node2.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
-        let d3 = node3.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
+        let d1 = node1.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
+        let d2 = node2.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
+        let d3 = node3.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
         
         //[nonce * L_i(0)] B8
-        let d1_ = public_nonce.mul_scalar(&node1.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).to_bigint());
-        let d2_ = public_nonce.mul_scalar(&node2.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).to_bigint());
-        let d3_ = public_nonce.mul_scalar(&node3.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).to_bigint());
+        let d1_ = public_nonce.mul_scalar(&node1.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap().to_bigint());
+        let d2_ = public_nonce.mul_scalar(&node2.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap().to_bigint());
+        let d3_ = public_nonce.mul_scalar(&node3.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap().to_bigint());
 
-        assert!(d1.equals(d1_));
-        assert!(d2.equals(d2_));
-        assert!(d3.equals(d3_));
+        assert!(d1.point().equals(d1_));
+        assert!(d2.point().equals(d2_));
+        assert!(d3.point().equals(d3_));
 
     }
 
@@ -399,15 +1258,18 @@ mod tests {
         let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
         let to_node2 = vec![&from_node1[1], &from_node2[1], &from_node3[1]];
         let to_node3 = vec![&from_node1[2], &from_node2[2], &from_node3[2]];
+        // every node also broadcasts commitments to its keygen polynomial so the evaluations can be checked
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
         // and finally each node reconstructs their part of the secret
-        node1.set_keyshare(&to_node1);
-        node2.set_keyshare(&to_node2);
-        node3.set_keyshare(&to_node3);
+        node1.set_keyshare(&to_node1, &commitments).unwrap();
+        node2.set_keyshare(&to_node2, &commitments).unwrap();
+        node3.set_keyshare(&to_node3, &commitments).unwrap();
 
         let secret_key_nobody_knows = 
-            node1.keygen_polynomial_at_0.clone() + 
-            node2.keygen_polynomial_at_0.clone() + 
-            node3.keygen_polynomial_at_0.clone() ;
+            node1.keygen_polynomial_at_0.expose_bigint() + 
+            node2.keygen_polynomial_at_0.expose_bigint() + 
+            node3.keygen_polynomial_at_0.expose_bigint() ;
 
 
         // some arbitrary nonce and public version
@@ -415,14 +1277,15 @@ mod tests {
         let public_nonce = B8.mul_scalar(nonce);
 
         // Try decrypting
-        let d1 = node1.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
-        let d2 = node2.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
-        let d3 = node3.partial_decrypt(&public_nonce, &nodes_to_decrypt_from);
-        assert!(d1.add(&d2).add(&d3).equals(
+        let d1 = node1.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
+        let d2 = node2.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
+        let d3 = node3.try_partial_decrypt_c1(&public_nonce, &nodes_to_decrypt_from).unwrap();
+        let dh_secret = reconstruct_dh_secret(vec![d1, d2, d3]);
+        assert!(dh_secret.equals(
             B8.mul_scalar(&(secret_key_nobody_knows)).mul_scalar(&nonce)
         ));
 
-        assert!(d1.add(&d2).add(&d3).equals(
+        assert!(dh_secret.equals(
             B8.mul_scalar(&nonce).mul_scalar(&(secret_key_nobody_knows))
         ));
     }
@@ -443,27 +1306,407 @@ mod tests {
         let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
         let to_node2 = vec![&from_node1[1], &from_node2[1], &from_node3[1]];
         let to_node3 = vec![&from_node1[2], &from_node2[2], &from_node3[2]];
+        // every node also broadcasts commitments to its keygen polynomial so the evaluations can be checked
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
         // and finally each node reconstructs their part of the secret
-        node1.set_keyshare(&to_node1);
-        node2.set_keyshare(&to_node2);
-        node3.set_keyshare(&to_node3);
+        node1.set_keyshare(&to_node1, &commitments).unwrap();
+        node2.set_keyshare(&to_node2, &commitments).unwrap();
+        node3.set_keyshare(&to_node3, &commitments).unwrap();
 
         
         let secret_key_nobody_knows = 
-            node1.keygen_polynomial_at_0.clone() + 
-            node2.keygen_polynomial_at_0.clone() + 
-            node3.keygen_polynomial_at_0.clone() ;
+            node1.keygen_polynomial_at_0.expose_bigint() + 
+            node2.keygen_polynomial_at_0.expose_bigint() + 
+            node3.keygen_polynomial_at_0.expose_bigint() ;
         
-        let mut result = node1.secret_lagrange_basis_at_0(&nodes_to_decrypt_from);
-        result.add_assign(  &node2.secret_lagrange_basis_at_0(&nodes_to_decrypt_from));
-        result.add_assign(  &node3.secret_lagrange_basis_at_0(&nodes_to_decrypt_from));
+        let mut result = node1.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap();
+        result.add_assign(  &node2.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap());
+        result.add_assign(  &node3.secret_lagrange_basis_at_0(&nodes_to_decrypt_from).unwrap());
         
         assert!(result.eq(&Fl::from_bigint(&secret_key_nobody_knows)), "failed to reconstruct secret key from lagrange bases");
 
     }
 
+    #[test]
+    fn test_keygen_commitment() {
+        let node = Node::init_rnd(1, 3, 3);
+        let commitment = node.keygen_commitment();
+        assert!(commitment.pubkey_share().unwrap().equals(node.pubkey_share().into_point()));
+        node.keygen_step1(3).iter().for_each(
+            |kh| assert!(commitment.verify(kh))
+        );
+    }
+
+    // A node that sends an evaluation inconsistent with its commitment should be named in the error
+    #[test]
+    fn test_set_keyshare_rejects_cheating_node() {
+        let [mut node1, node2, node3] = init_test_nodes::<3,3>();
+        let from_node1 = node1.keygen_step1(3);
+        let from_node2 = node2.keygen_step1(3);
+        let mut from_node3 = node3.keygen_step1(3);
+        // node 3 tampers with what it sends node 1
+        from_node3[0].value += BigInt::from(1u8);
+
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
+        let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
+
+        match node1.set_keyshare(&to_node1, &commitments) {
+            Err(KeygenError::InvalidShare(cheater)) => assert_eq!(cheater, 3),
+            _ => panic!("expected node 3's share to be rejected")
+        }
+        assert!(node1.keyshare.is_none());
+
+        // it also shouldn't be possible to pass off another node's evaluation, or one meant for another node
+        let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node2[0]];
+        assert!(matches!(node1.set_keyshare(&to_node1, &commitments), Err(KeygenError::DuplicateSender(2))));
+        let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[1]];
+        assert!(matches!(node1.set_keyshare(&to_node1, &commitments), Err(KeygenError::WrongRecipient { from_node: 3, for_node: 2 })));
+    }
+
+    #[test]
+    fn test_decrypt_verified() {
+        let [mut node1, mut node2, mut node3] = init_test_nodes::<3,3>();
+        let nodes_to_decrypt_from: Vec<u32> = vec![1,2,3];
+        let from_node1 = node1.keygen_step1(3);
+        let from_node2 = node2.keygen_step1(3);
+        let from_node3 = node3.keygen_step1(3);
+        let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
+        let to_node2 = vec![&from_node1[1], &from_node2[1], &from_node3[1]];
+        let to_node3 = vec![&from_node1[2], &from_node2[2], &from_node3[2]];
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
+        node1.set_keyshare(&to_node1, &commitments).unwrap();
+        node2.set_keyshare(&to_node2, &commitments).unwrap();
+        node3.set_keyshare(&to_node3, &commitments).unwrap();
+
+        // the verification keys can be computed by anyone from the commitments
+        let commitments: Vec<KeygenCommitment> = commitments.into_iter().cloned().collect();
+        let verification_keys: Vec<Point> = (1..4).map(|i| verification_key(&commitments, i)).collect();
+        assert!(verification_keys[0].equals(node1.verification_key().unwrap()));
+        assert!(verification_keys[2].equals(node3.verification_key().unwrap()));
+
+        let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+        let shared_pubkey = calculate_pubkey(
+            vec![node1.pubkey_share(), node2.pubkey_share(), node3.pubkey_share()]
+        ).unwrap();
+        let encrypt = || encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+        let encrypted = encrypt();
+
+        let shares = || vec![
+            node1.try_partial_decrypt_c1_with_proof(encrypted.c1(), &nodes_to_decrypt_from).unwrap(),
+            node2.try_partial_decrypt_c1_with_proof(encrypted.c1(), &nodes_to_decrypt_from).unwrap(),
+            node3.try_partial_decrypt_c1_with_proof(encrypted.c1(), &nodes_to_decrypt_from).unwrap(),
+        ];
+        let decrypted = decrypt_verified(encrypt(), shares(), &verification_keys, 3).unwrap();
+        assert!(some_msg.equals(decrypted));
+
+        // the proofs are plain babyjubjub-rs DLEQProofs, which check on their own and serialize like the OPRF server's
+        let share = &shares()[0];
+        assert!(share.proof().verify());
+        let proof: DLEQProof = serde_json::from_str(&serde_json::to_string(share.proof()).unwrap()).unwrap();
+        assert!(proof.verify() && proof.xB.equals(share.share().clone()));
+
+        // node 1's proof has the wrong response
+        let mut bad_shares = shares();
+        bad_shares[0].proof.s += 1u8;
+        assert!(matches!(decrypt_verified(encrypt(), bad_shares, &verification_keys, 3), Err(DecryptionError::InvalidShare(1))));
+
+        // node 2 sends a share that isn't what its proof is about
+        let mut bad_shares = shares();
+        bad_shares[1].share = bad_shares[1].share.add(&B8);
+        assert!(matches!(decrypt_verified(encrypt(), bad_shares, &verification_keys, 3), Err(DecryptionError::InvalidShare(2))));
+
+        // node 3 sends a share with a valid proof, but for a key other than its keyshare
+        let mut bad_shares = shares();
+        bad_shares[2] = node1.try_partial_decrypt_c1_with_proof(encrypted.c1(), &nodes_to_decrypt_from).unwrap();
+        bad_shares[2].node_idx = 3;
+        assert!(matches!(decrypt_verified(encrypt(), bad_shares, &verification_keys, 3), Err(DecryptionError::InvalidShare(3))));
+
+        // no shares, and the same valid share twice, which would make the Lagrange basis 1 and the proofs pass
+        assert!(matches!(decrypt_verified(encrypt(), vec![], &verification_keys, 0), Err(DecryptionError::WrongNumberOfShares { provided: 0, required: 0 })));
+        let twice = vec![
+            node1.try_partial_decrypt_c1_with_proof(encrypted.c1(), &vec![1, 2]).unwrap(),
+            node1.try_partial_decrypt_c1_with_proof(encrypted.c1(), &vec![1, 2]).unwrap(),
+        ];
+        assert!(matches!(decrypt_verified(encrypt(), twice, &verification_keys, 2), Err(DecryptionError::DuplicateNode(1))));
+    }
+
+    // Every set of at least threshold_nodes nodes should be able to decrypt
+    #[test]
+    fn test_threshold_decrypt_every_subset() {
+        for (threshold_nodes, total_nodes) in [(1,1), (1,3), (2,3), (3,3), (2,4), (3,5)] {
+            let nodes = init_keyshared_nodes(threshold_nodes, total_nodes);
+            let shared_pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+            let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+            let all_indices: Vec<u32> = (1..(total_nodes as u32)+1).collect();
+
+            for size in threshold_nodes..total_nodes+1 {
+                for nodes_to_decrypt_from in subsets(&all_indices, size) {
+                    let encrypted = encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+                    let shares: Vec<DecryptionShare> = nodes_to_decrypt_from.iter().map(
+                        |i| nodes[*i as usize - 1].try_partial_decrypt_c1(encrypted.c1(), &nodes_to_decrypt_from).unwrap()
+                    ).collect();
+                    let decrypted = decrypt(encrypted, shares, size as u64);
+                    assert!(some_msg.equals(decrypted), "{}-of-{} decryption failed with nodes {:?}", threshold_nodes, total_nodes, nodes_to_decrypt_from);
+                }
+            }
+        }
+    }
+
+    // Raw shares gathered once should decrypt with any subset of them
+    #[test]
+    fn test_decrypt_raw() {
+        let nodes = init_keyshared_nodes(3, 5);
+        let shared_pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+        let encrypt = || encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+
+        let c1 = encrypt().c1().clone();
+        let raw_shares: Vec<RawDecryptionShare> = nodes.iter().map(|n| n.try_raw_partial_decrypt_c1(&c1).unwrap()).collect();
+        for subset in subsets(&[1,2,3,4,5], 3) {
+            let shares: Vec<RawDecryptionShare> = subset.iter().map(|i| raw_shares[*i as usize - 1].clone()).collect();
+            assert!(some_msg.equals(decrypt_raw(encrypt(), &shares, 3)), "failed to decrypt with raw shares from {:?}", subset);
+        }
+
+        // a bad share makes decryption fail, but the other shares can still be used without asking the nodes again
+        let mut shares = raw_shares.clone();
+        shares[2].share = shares[2].share.add(&B8);
+        assert!(!some_msg.equals(decrypt_raw(encrypt(), &shares, 3)));
+        shares.swap(2, 4);
+        assert!(some_msg.equals(decrypt_raw(encrypt(), &shares, 3)));
+    }
+
+    // Refreshing should change every keyshare but not the secret key they share
+    #[test]
+    fn test_refresh_keyshares() {
+        let mut nodes = init_keyshared_nodes(3, 5);
+        let shared_pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let old_shares: Vec<BigInt> = nodes.iter().map(|n| n.keyshare.as_ref().unwrap().share.expose_bigint()).collect();
+        let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+        let encrypt = || encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+        // raw share from before the refresh
+        let stale_share = nodes[0].try_raw_partial_decrypt_c1(encrypt().c1()).unwrap();
+
+        let refresh: Vec<(Vec<KeygenHelper>, KeygenCommitment)> = nodes.iter().map(|n| n.refresh_step1()).collect();
+        let commitments: Vec<&KeygenCommitment> = refresh.iter().map(|(_, c)| c).collect();
+        nodes.iter_mut().enumerate().for_each(|(i, node)| {
+            let for_me: Vec<&KeygenHelper> = refresh.iter().map(|(helpers, _)| &helpers[i]).collect();
+            node.refresh_keyshare(&for_me, &commitments).unwrap();
+        });
+
+        nodes.iter().zip(old_shares.iter()).for_each(
+            |(n, old)| assert!(*n.keyshare.as_ref().unwrap().share.expose_secret() != Fl::from_bigint(old))
+        );
+        assert!(shared_pubkey.equals(calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap().into_point()));
+
+        let fresh_shares: Vec<RawDecryptionShare> = nodes.iter().map(|n| n.try_raw_partial_decrypt_c1(encrypt().c1()).unwrap()).collect();
+        assert!(some_msg.equals(decrypt_raw(encrypt(), &fresh_shares, 3)));
+        // shares from before and after the refresh can't be mixed
+        let mixed = vec![stale_share, fresh_shares[1].clone(), fresh_shares[2].clone()];
+        assert!(!some_msg.equals(decrypt_raw(encrypt(), &mixed, 3)));
+    }
+
+    #[test]
+    fn test_refresh_rejects_nonzero_constant() {
+        let mut nodes = init_keyshared_nodes(2, 2);
+        // node 2 tries to shift the secret key by using a normal keygen polynomial for its refresh
+        let sneaky = Node::init_rnd(2, 2, 2);
+        let refresh = vec![nodes[0].refresh_step1(), (sneaky.keygen_step1(2), sneaky.keygen_commitment())];
+        let commitments: Vec<&KeygenCommitment> = refresh.iter().map(|(_, c)| c).collect();
+        let for_node1: Vec<&KeygenHelper> = refresh.iter().map(|(helpers, _)| &helpers[0]).collect();
+        assert!(matches!(nodes[0].refresh_keyshare(&for_node1, &commitments), Err(Error::Keygen(KeygenError::InvalidCommitment(2)))));
+    }
+
+    #[test]
+    fn test_refresh_without_keyshare() {
+        let mut node = Node::init_rnd(1, 2, 2);
+        let refresh = node.refresh_step1();
+        assert!(matches!(node.refresh_keyshare(&vec![&refresh.0[0]], &vec![&refresh.1]), Err(Error::MissingKeyshare)));
+    }
+
+    // Moves the key from `old_nodes` of the old committee to a new committee, returning the new committee
+    fn reshare(old: &Vec<Node>, old_nodes: &Vec<u32>, new_threshold_nodes: usize, new_total_nodes: usize) -> Vec<Node> {
+        let old_verification_keys: Vec<Point> = old.iter().map(|n| n.verification_key().unwrap()).collect();
+        let subshares: Vec<(Vec<KeygenHelper>, KeygenCommitment)> = old_nodes.iter().map(
+            |i| old[*i as usize - 1].reshare_step1(new_threshold_nodes, new_total_nodes).unwrap()
+        ).collect();
+        let commitments: Vec<&KeygenCommitment> = subshares.iter().map(|(_, c)| c).collect();
+        (1..new_total_nodes+1).map(|j| {
+            let for_me: Vec<&KeygenHelper> = subshares.iter().map(|(helpers, _)| &helpers[j-1]).collect();
+            Node::init_from_reshare(j, new_threshold_nodes, new_total_nodes, old[0].threshold_nodes, old_nodes, &for_me, &commitments, &old_verification_keys).unwrap()
+        }).collect()
+    }
+
+    #[test]
+    fn test_reshare_to_new_committee() {
+        let old = init_keyshared_nodes(2, 2);
+        let shared_pubkey = calculate_pubkey(old.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+        let encrypt = || encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+
+        // 2-of-2 to 3-of-5
+        let new = reshare(&old, &vec![1, 2], 3, 5);
+        assert!(new.iter().all(|n| n.threshold_nodes == 3 && n.total_nodes == 5));
+        for subset in subsets(&[1,2,3,4,5], 3) {
+            let shares: Vec<RawDecryptionShare> = subset.iter().map(|i| new[*i as usize - 1].try_raw_partial_decrypt_c1(encrypt().c1()).unwrap()).collect();
+            assert!(some_msg.equals(decrypt_raw(encrypt(), &shares, 3)), "failed to decrypt with new nodes {:?}", subset);
+        }
+        let shares: Vec<RawDecryptionShare> = new[..2].iter().map(|n| n.try_raw_partial_decrypt_c1(encrypt().c1()).unwrap()).collect();
+        assert!(!some_msg.equals(decrypt_raw(encrypt(), &shares, 2)));
+
+        // and then 3-of-5 to 2-of-3 using only some of the 3-of-5 nodes
+        let newer = reshare(&new, &vec![2, 4, 5], 2, 3);
+        let shares: Vec<RawDecryptionShare> = newer[1..].iter().map(|n| n.try_raw_partial_decrypt_c1(encrypt().c1()).unwrap()).collect();
+        assert!(some_msg.equals(decrypt_raw(encrypt(), &shares, 2)));
+    }
+
+    #[test]
+    fn test_reshared_pubkey() {
+        let old = init_keyshared_nodes(3, 4);
+        let shared_pubkey = calculate_pubkey(old.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let old_nodes: Vec<u32> = vec![1, 3, 4];
+        let subshares: Vec<(Vec<KeygenHelper>, KeygenCommitment)> = old_nodes.iter().map(|i| old[*i as usize - 1].reshare_step1(2, 2).unwrap()).collect();
+        let commitments: Vec<&KeygenCommitment> = subshares.iter().map(|(_, c)| c).collect();
+        assert!(shared_pubkey.equals(reshared_pubkey(&commitments, &old_nodes, 3).unwrap().into_point()));
+
+        assert!(matches!(reshared_pubkey(&commitments[..2].to_vec(), &old_nodes, 3), Err(Error::Keygen(KeygenError::MissingCommitment(4)))));
+        assert!(matches!(reshared_pubkey(&commitments, &vec![1, 3], 3), Err(Error::Keygen(KeygenError::InvalidOldNodes(_)))));
+        assert!(matches!(reshared_pubkey(&commitments, &vec![1, 3, 3], 3), Err(Error::Keygen(KeygenError::InvalidOldNodes(_)))));
+    }
+
+    // An old node can't reshare something other than its keyshare
+    #[test]
+    fn test_reshare_rejects_wrong_constant() {
+        let old = init_keyshared_nodes(2, 2);
+        let old_verification_keys: Vec<Point> = old.iter().map(|n| n.verification_key().unwrap()).collect();
+        let honest = old[0].reshare_step1(2, 3).unwrap();
+        let sneaky = Node::init_rnd(2, 2, 3);
+        let sneaky = (sneaky.keygen_step1(3), sneaky.keygen_commitment());
+        let commitments = vec![&honest.1, &sneaky.1];
+        let for_node1 = vec![&honest.0[0], &sneaky.0[0]];
+        assert!(matches!(
+            Node::init_from_reshare(1, 2, 3, 2, &vec![1, 2], &for_node1, &commitments, &old_verification_keys),
+            Err(Error::Keygen(KeygenError::InvalidCommitment(2)))
+        ));
+    }
+
+    #[test]
+    fn test_reshare_errors() {
+        let old = init_keyshared_nodes(2, 3);
+        let old_verification_keys: Vec<Point> = old.iter().map(|n| n.verification_key().unwrap()).collect();
+        assert!(matches!(Node::init_rnd(1, 2, 3).reshare_step1(2, 3), Err(Error::MissingKeyshare)));
+        assert!(matches!(old[0].reshare_step1(4, 3), Err(Error::InvalidThreshold { .. })));
+
+        let subshares: Vec<(Vec<KeygenHelper>, KeygenCommitment)> = old[..2].iter().map(|n| n.reshare_step1(2, 3).unwrap()).collect();
+        let commitments: Vec<&KeygenCommitment> = subshares.iter().map(|(_, c)| c).collect();
+        let for_node1: Vec<&KeygenHelper> = subshares.iter().map(|(helpers, _)| &helpers[0]).collect();
+        let init = |idx: usize, old_nodes: &Vec<u32>, evals: &Vec<&KeygenHelper>|
+            Node::init_from_reshare(idx, 2, 3, 2, old_nodes, evals, &commitments, &old_verification_keys);
+        assert!(matches!(init(0, &vec![1, 2], &for_node1), Err(Error::InvalidNodeIndex { idx: 0, total_nodes: 3 })));
+        assert!(matches!(init(4, &vec![1, 2], &for_node1), Err(Error::InvalidNodeIndex { idx: 4, total_nodes: 3 })));
+        // one old node on its own can't reshare a 2-of-3 key, even if it names itself twice
+        assert!(matches!(init(1, &vec![1], &vec![for_node1[0]]), Err(Error::Keygen(KeygenError::InvalidOldNodes(_)))));
+        assert!(matches!(init(1, &vec![1, 1], &vec![for_node1[0], for_node1[0]]), Err(Error::Keygen(KeygenError::InvalidOldNodes(_)))));
+        assert!(matches!(init(1, &vec![0, 2], &for_node1), Err(Error::Keygen(KeygenError::InvalidOldNodes(_)))));
+
+        let new = init(1, &vec![1, 2], &for_node1).unwrap();
+        assert!(matches!(new.try_pubkey_share(), Err(Error::ResharedNode)));
+        assert!(old[0].try_pubkey_share().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "more than one share from node 2")]
+    fn test_combine_raw_shares_duplicate() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let share = nodes[1].try_raw_partial_decrypt_c1(&B8).unwrap();
+        combine_raw_shares(&[share.clone(), share]);
+    }
+
+    #[test]
+    fn test_rerandomize() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let shared_pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
+        let encrypted = encrypt(&shared_pubkey, &7654321.to_bigint().unwrap(), &some_msg);
+
+        let (rerandomized, proof) = rerandomize_with_proof(&shared_pubkey, &encrypted).unwrap();
+        assert!(!rerandomized.c1().equals(encrypted.c1().clone()));
+        assert!(!rerandomized.c2().equals(encrypted.c2().clone()));
+        assert!(proof.verify(&shared_pubkey, &encrypted, &rerandomized));
+
+        // the proof is only for this pair of ciphertexts
+        let again = rerandomize(&shared_pubkey, &rerandomized);
+        assert!(!proof.verify(&shared_pubkey, &encrypted, &again));
+        let mauled = Ciphertext::new(rerandomized.c1().clone(), rerandomized.c2().add(&B8)).unwrap();
+        assert!(!proof.verify(&shared_pubkey, &encrypted, &mauled));
+
+        let shares = vec![
+            nodes[0].try_partial_decrypt_c1(again.c1(), &vec![1, 2]).unwrap(),
+            nodes[1].try_partial_decrypt_c1(again.c1(), &vec![1, 2]).unwrap(),
+        ];
+        assert!(decrypt(again, shares, 2).equals(some_msg));
+    }
+
+    #[test]
+    fn test_try_variants_return_errors() {
+        assert!(matches!(Node::try_init_rnd(0, 2, 3), Err(Error::InvalidNodeIndex { idx: 0, total_nodes: 3 })));
+        assert!(matches!(Node::try_init_rnd(4, 2, 3), Err(Error::InvalidNodeIndex { idx: 4, total_nodes: 3 })));
+        assert!(matches!(Node::try_init_rnd(1, 0, 3), Err(Error::InvalidThreshold { .. })));
+        assert!(matches!(Node::try_init_rnd(1, 4, 3), Err(Error::InvalidThreshold { .. })));
+        assert!(matches!(Node::try_init_from_seed(&vec![1u8; 31], 1, 2, 3), Err(Error::InvalidSeed(31))));
+
+        let no_keyshare = Node::init_rnd(1, 2, 3);
+        assert!(matches!(no_keyshare.try_partial_decrypt_c1(&B8, &vec![1, 2]), Err(Error::MissingKeyshare)));
+        assert!(matches!(no_keyshare.try_raw_partial_decrypt_c1(&B8), Err(Error::MissingKeyshare)));
+
+        let nodes = init_keyshared_nodes(2, 3);
+        let off_curve = Point { x: Fr::one(), y: Fr::one() };
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&off_curve, &vec![1, 2]), Err(Error::NotOnCurve)));
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&B8, &vec![1]), Err(Error::InvalidDecryptingNodes(_))));
+        assert!(nodes[0].try_partial_decrypt_c1(&B8, &vec![1, 2]).is_ok());
+
+        assert!(matches!(try_calculate_pubkey(vec![]), Err(Error::NoPubkeyShares)));
+        assert!(matches!(PubkeyShare::new(off_curve.clone()), Err(Error::NotOnCurve)));
+
+        let encrypted = encrypt(&PublicKey::new(B8.clone()).unwrap(), &7654321.to_bigint().unwrap(), &B8);
+        assert!(matches!(
+            try_decrypt(encrypted.clone(), vec![nodes[0].try_partial_decrypt_c1(encrypted.c1(), &vec![1, 2]).unwrap()], 2),
+            Err(Error::Decryption(DecryptionError::WrongNumberOfShares { provided: 1, required: 2 }))
+        ));
+        let share = nodes[0].try_partial_decrypt_c1(encrypted.c1(), &vec![1, 2]).unwrap();
+        assert!(matches!(
+            try_decrypt(encrypted, vec![share.clone(), share], 2),
+            Err(Error::InvalidDecryptingNodes(_))
+        ));
+    }
+
+    #[test]
+    fn test_partial_decrypt_too_few_nodes() {
+        let nodes = init_keyshared_nodes(3, 5);
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&B8, &vec![1, 2]), Err(Error::InvalidDecryptingNodes(_))));
+    }
+
+    #[test]
+    fn test_partial_decrypt_duplicate_nodes() {
+        let nodes = init_keyshared_nodes(2, 3);
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&B8, &vec![1, 2, 2]), Err(Error::InvalidDecryptingNodes(_))));
+    }
+
+    #[test]
+    fn test_partial_decrypt_without_self() {
+        let nodes = init_keyshared_nodes(2, 3);
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&B8, &vec![2, 3]), Err(Error::InvalidDecryptingNodes(_))));
+    }
+
+    #[test]
+    fn test_partial_decrypt_out_of_range() {
+        let nodes = init_keyshared_nodes(2, 3);
+        assert!(matches!(nodes[0].try_partial_decrypt_c1(&B8, &vec![1, 4]), Err(Error::InvalidNodeIndex { idx: 4, total_nodes: 3 })));
+    }
+
     // TODO: separate this into smaller unit tests
-    // TODO: try with more total nodes than threshold nodes
     #[test]
     fn test_encrypt_decrypt() {
 
@@ -477,10 +1720,13 @@ mod tests {
         let to_node1 = vec![&from_node1[0], &from_node2[0], &from_node3[0]];
         let to_node2 = vec![&from_node1[1], &from_node2[1], &from_node3[1]];
         let to_node3 = vec![&from_node1[2], &from_node2[2], &from_node3[2]];
+        // every node also broadcasts commitments to its keygen polynomial so the evaluations can be checked
+        let commitments = vec![node1.keygen_commitment(), node2.keygen_commitment(), node3.keygen_commitment()];
+        let commitments: Vec<&KeygenCommitment> = commitments.iter().collect();
         // and finally each node reconstructs their part of the secret
-        node1.set_keyshare(&to_node1);
-        node2.set_keyshare(&to_node2);
-        node3.set_keyshare(&to_node3);
+        node1.set_keyshare(&to_node1, &commitments).unwrap();
+        node2.set_keyshare(&to_node2, &commitments).unwrap();
+        node3.set_keyshare(&to_node3, &commitments).unwrap();
 
         // Try encrypting a message
         let some_msg = B8.mul_scalar(&123456789.to_bigint().unwrap());
@@ -490,11 +1736,11 @@ mod tests {
         let nonce = &7654321.to_bigint().unwrap();
 
         // Check C2 was computed correctly
-        let encrypted = encrypt_elgamal(&shared_pubkey, nonce, &some_msg);
+        let encrypted = encrypt(&shared_pubkey, nonce, &some_msg);
 
-        let d1 = node1.partial_decrypt(&encrypted.c1, &nodes_to_decrypt_from);
-        let d2 = node2.partial_decrypt(&encrypted.c1, &nodes_to_decrypt_from);
-        let d3 = node3.partial_decrypt(&encrypted.c1, &nodes_to_decrypt_from);
+        let d1 = node1.try_partial_decrypt_c1(encrypted.c1(), &nodes_to_decrypt_from).unwrap();
+        let d2 = node2.try_partial_decrypt_c1(encrypted.c1(), &nodes_to_decrypt_from).unwrap();
+        let d3 = node3.try_partial_decrypt_c1(encrypted.c1(), &nodes_to_decrypt_from).unwrap();
 
         let decrypted = decrypt(encrypted, vec![d1,d2,d3], 3);
         assert!(some_msg.equals(decrypted));
@@ -3,7 +3,9 @@ use clap::{Subcommand, Args, Parser};
 use ff::PrimeField;
 use num_bigint::{RandBigInt, ToBigInt};
 use std::env;
-use babyjubjub_rs::{encrypt_elgamal, Fr, PrivateKey, ElGamalEncryption, Point, Q, ToDecimalString};
+use babyjubjub_rs::{Fr, PrivateKey, ElGamalEncryption, Point, Q, ToDecimalString};
+use babyjubjub_elgamal::bsgs::BsgsTable;
+use babyjubjub_elgamal::fixed_base::encrypt_elgamal;
 
 /// BabyJubJub ElGamal
 #[derive(Parser)]
@@ -19,6 +21,8 @@ enum Commands {
     Encrypt(Encrypt),
     /// Decrypts a point given the private key in the envrionment variable "ELGAMAL_PRIVKEY_HOLONYM"
     Decrypt(Decrypt),
+    /// Builds a baby-step giant-step table for decoding exponential ElGamal messages and saves it to a file
+    BsgsTable(BsgsTableArgs),
 }
 
 #[derive(Args)]
@@ -52,19 +56,31 @@ struct Decrypt {
     c2y: String,
 }
 
-fn main() {
-    let ds = Point::from_xy_strings("69".to_string(), "70".to_string());
-    println!("ds {:?}", ds.x.to_dec_string());
+#[derive(Args)]
+struct BsgsTableArgs {
+    /// largest message the table should decode
+    #[arg(long)]
+    max: u64,
+    /// file to save the table to
+    #[arg(long)]
+    out: String,
+}
+
+fn private_key() -> PrivateKey {
     let p = match env::var("ELGAMAL_PRIVKEY_HOLONYM") {
         Ok(privkey) => privkey,
         Err(_) => {
             panic!("ELGAMAL_PRIVKEY_HOLONYM does not exist. It should be a 32-byte hex string such as 0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef but random")
         }
     };
-    let private_key = PrivateKey::import(
+    PrivateKey::import(
         hex::decode(p).unwrap(),
-    ).unwrap();
+    ).unwrap()
+}
 
+fn main() {
+    let ds = Point::from_xy_strings("69".to_string(), "70".to_string());
+    println!("ds {:?}", ds.x.to_dec_string());
 
     // let nonce = rand::thread_rng().gen_bigint_range(&0.to_bigint().unwrap() , &Q);
     // let some_point = B8.mul_scalar(&BigInt::from_u8(0x69).unwrap());
@@ -86,7 +102,7 @@ fn main() {
                 y: Fr::from_str(&e.my).unwrap()
             }
         )),
-        Commands::Decrypt(d) => println!("{:?}", private_key.decrypt_elgamal(ElGamalEncryption {
+        Commands::Decrypt(d) => println!("{:?}", private_key().decrypt_elgamal(ElGamalEncryption {
             c1: Point { 
                 x: Fr::from_str(&d.c1x).unwrap(),
                 y: Fr::from_str(&d.c1y).unwrap(),
@@ -95,7 +111,11 @@ fn main() {
                 x: Fr::from_str(&d.c2x).unwrap(),
                 y: Fr::from_str(&d.c2y).unwrap()
             }
-        }))
+        })),
+        Commands::BsgsTable(b) => {
+            BsgsTable::build(b.max).save(&b.out).unwrap();
+            println!("saved table for messages up to {} to {}", b.max, b.out)
+        }
     };    
 
     
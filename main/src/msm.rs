@@ -0,0 +1,121 @@
+/*
+Multi-scalar multiplication
+
+Combining shares, reconstructing public keys and checking commitments all compute sums ∑ k_i*P_i. Doing each multiplication separately costs a full
+double-and-add per point; `multi_scalar_mul` uses Pippenger's bucket method instead:
+    - the scalars are split into c-bit windows
+    - for each window, each point is added to the bucket for its scalar's digit in that window, and the buckets are combined into ∑ d*bucket_d with running sums
+    - the windows' sums are combined from the top, doubling c times in between
+so the doublings are shared by all the points, and there are roughly n + 2^c additions per window. For a handful of points, c is small and this is close to Straus's
+interleaved method. All of it is in projective coordinates, converting to affine once at the end.
+None of this is constant time, so it's only for public scalars, such as Lagrange coefficients and the random coefficients of batch verification.
+*/
+use num_bigint::{BigInt, Sign};
+use babyjubjub_rs::Point;
+
+use crate::Error;
+use crate::constant_time::ProjectivePoint;
+
+/// ∑ `scalars[i]`*`points[i]`. Gives the same result as multiplying with `Point::mul_scalar` and adding; a negative scalar -k multiplies the negated point by k.
+/// The sum of no points is the identity (0, 1). Fails if there isn't exactly one scalar for each point
+pub fn multi_scalar_mul(points: &[Point], scalars: &[BigInt]) -> Result<Point, Error> {
+    if points.len() != scalars.len() {
+        return Err(Error::MismatchedLengths { points: points.len(), scalars: scalars.len() });
+    }
+    let (points, scalars): (Vec<ProjectivePoint>, Vec<Vec<u8>>) = points.iter().zip(scalars.iter()).map(|(p, k)| {
+        let (sign, bytes) = k.to_bytes_le();
+        let p = if sign == Sign::Minus { p.neg() } else { p.clone() };
+        (ProjectivePoint::from_affine(&p), bytes)
+    }).unzip();
+    let bits = scalars.iter().map(|k| k.len() * 8).max().unwrap_or(0);
+    let c = window_bits(points.len());
+
+    let mut acc = ProjectivePoint::identity();
+    for window in (0..(bits + c - 1) / c).rev() {
+        for _ in 0..c {
+            acc = acc.add(&acc);
+        }
+        // buckets[d-1] is the sum of the points whose digit in this window is d
+        let mut buckets = vec![ProjectivePoint::identity(); (1 << c) - 1];
+        for (p, k) in points.iter().zip(scalars.iter()) {
+            let d = digit(k, window * c, c);
+            if d != 0 {
+                buckets[d - 1] = buckets[d - 1].add(p);
+            }
+        }
+        // going down from the top bucket, `running` is the sum of buckets d and up, and adding up those running sums counts bucket d d times
+        let mut running = ProjectivePoint::identity();
+        let mut window_sum = ProjectivePoint::identity();
+        for bucket in buckets.iter().rev() {
+            running = running.add(bucket);
+            window_sum = window_sum.add(&running);
+        }
+        acc = acc.add(&window_sum);
+    }
+    Ok(acc.to_affine())
+}
+
+/// ∑ `points[i]`, with a single conversion to affine coordinates. The sum of no points is the identity (0, 1)
+pub fn sum_points(points: &[Point]) -> Point {
+    points.iter().fold(
+        ProjectivePoint::identity(),
+        |acc, p| acc.add(&ProjectivePoint::from_affine(p))
+    ).to_affine()
+}
+
+// Roughly ln(n), which minimizes the number of additions
+fn window_bits(n: usize) -> usize {
+    if n < 32 {
+        3
+    } else {
+        (n as f64).ln().ceil() as usize
+    }
+}
+
+// The `len` bits of the little-endian `bytes` starting at bit `start`
+fn digit(bytes: &[u8], start: usize, len: usize) -> usize {
+    (start..start + len).rev().fold(0, |d, i| {
+        let bit = bytes.get(i / 8).map_or(0, |b| (b >> (i % 8)) & 1);
+        (d << 1) | bit as usize
+    })
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use num_bigint::RandBigInt;
+    use babyjubjub_rs::{B8, SUBORDER};
+
+    #[test]
+    fn test_matches_mul_scalar() {
+        for n in [0, 1, 2, 5, 40] {
+            let points: Vec<Point> = (0..n).map(|i| B8.mul_scalar(&BigInt::from(1000u32 + i))).collect();
+            let mut scalars: Vec<BigInt> = (0..n).map(|_| rand::thread_rng().gen_bigint_range(&BigInt::from(0u8), &SUBORDER)).collect();
+            if n > 1 {
+                scalars[0] = BigInt::from(0u8);
+                scalars[1] = BigInt::from(1u8);
+            }
+            let identity = B8.add(&B8.neg());
+            let expected = points.iter().zip(scalars.iter()).fold(
+                identity.clone(),
+                |acc, (p, k)| acc.add(&p.mul_scalar(k))
+            );
+            assert!(multi_scalar_mul(&points, &scalars).unwrap().equals(expected), "wrong result for {} points", n);
+
+            let sum = points.iter().fold(identity, |acc, p| acc.add(p));
+            assert!(sum_points(&points).equals(sum));
+        }
+
+        // scalars bigger than SUBORDER aren't reduced, the same as mul_scalar
+        let big = &*SUBORDER * 3u8 + 7u8;
+        assert!(multi_scalar_mul(&[B8.clone()], &[big.clone()]).unwrap().equals(B8.mul_scalar(&big)));
+
+        // a negative scalar multiplies the negated point
+        let p = B8.mul_scalar(&BigInt::from(4321u32));
+        let expected = p.mul_scalar(&BigInt::from(5u8)).add(&B8.neg().mul_scalar(&BigInt::from(12u8)));
+        assert!(multi_scalar_mul(&[p.clone(), B8.clone()], &[BigInt::from(5), BigInt::from(-12)]).unwrap().equals(expected));
+
+        assert!(matches!(multi_scalar_mul(&[p, B8.clone()], &[BigInt::from(5)]), Err(Error::MismatchedLengths { points: 2, scalars: 1 })));
+    }
+}
@@ -0,0 +1,101 @@
+/*
+Multi-recipient ElGamal encryption
+
+Encrypting the same message to several public keys normally takes a fresh nonce, and so a fresh C1, per key. Here one nonce r is shared:
+C1 = r*B8 once, and C2_j = M + r*PK_j for each recipient j. Reusing r this way is as secure as separate encryptions as long as every
+public key was generated independently (Kurosawa, "Multi-recipient Public-Key Encryption with Shortened Ciphertext"; Bellare, Boldyreva and Staddon,
+"Randomness Re-use in Multi-recipient Encryption Schemes"). It should not be used with a public key someone chose after seeing the others.
+
+Recipient j's ciphertext is just (C1, C2_j), so it is decrypted with `PrivateKey::decrypt_elgamal` or, for a committee, the usual threshold decryption.
+Committees partially decrypt the same C1 with `Node::partial_decrypt_c1`, each with their own keyshares
+*/
+use num_bigint::{BigInt, RandBigInt};
+use babyjubjub_rs::{Point, ElGamalEncryption, SUBORDER};
+use serde::{Serialize, Deserialize};
+
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+use crate::polynomial::to_fl;
+use crate::PublicKey;
+
+/// One message encrypted to several public keys, sharing `c1`. `c2s[j]` is for the j'th public key it was encrypted to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MultiRecipientEncryption {
+    pub c1: Point,
+    pub c2s: Vec<Point>,
+}
+
+impl MultiRecipientEncryption {
+    /// The ciphertext for the `recipient`'th public key, which can be decrypted like any other ElGamal encryption
+    pub fn for_recipient(&self, recipient: usize) -> Option<ElGamalEncryption> {
+        self.c2s.get(recipient).map(
+            |c2| ElGamalEncryption { c1: self.c1.clone(), c2: c2.clone() }
+        )
+    }
+
+    pub fn num_recipients(&self) -> usize {
+        self.c2s.len()
+    }
+}
+
+/// Encrypts `msg` to every key in `pubkeys` with a random nonce
+pub fn encrypt_multi(pubkeys: &Vec<PublicKey>, msg: &Point) -> MultiRecipientEncryption {
+    let nonce = rand::thread_rng().gen_bigint_range(&BigInt::from(1u8), &SUBORDER);
+    encrypt_multi_with_nonce(pubkeys, &nonce, msg)
+}
+
+/// Encrypts `msg` to every key in `pubkeys` using `nonce` for all of them. The nonce must be secret and never reused for another message
+pub fn encrypt_multi_with_nonce(pubkeys: &Vec<PublicKey>, nonce: &BigInt, msg: &Point) -> MultiRecipientEncryption {
+    let nonce = to_fl(nonce);
+    MultiRecipientEncryption {
+        c1: mul_b8_ct(&nonce),
+        c2s: pubkeys.iter().map(
+            |pk| msg.add(&mul_scalar_ct(pk, &nonce))
+        ).collect()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::{PrivateKey, B8, encrypt_elgamal};
+    use crate::{Ciphertext, calculate_pubkey, decrypt};
+    use crate::tests::init_keyshared_nodes;
+
+    // Each recipient should be able to decrypt their part, whether they're a single private key or a threshold committee
+    #[test]
+    fn test_multi_recipient() {
+        let committee1 = init_keyshared_nodes(2, 3);
+        let committee2 = init_keyshared_nodes(3, 3);
+        let single = PrivateKey::import(vec![7u8; 32]).unwrap();
+        let pubkeys = vec![
+            calculate_pubkey(committee1.iter().map(|n| n.pubkey_share()).collect()).unwrap(),
+            calculate_pubkey(committee2.iter().map(|n| n.pubkey_share()).collect()).unwrap(),
+            PublicKey::new(single.public()).unwrap(),
+        ];
+        let msg = B8.mul_scalar(&BigInt::from(123456789u32));
+        let nonce = BigInt::from(7654321u32);
+        let encrypted = encrypt_multi_with_nonce(&pubkeys, &nonce, &msg);
+        assert_eq!(encrypted.num_recipients(), 3);
+        assert!(encrypted.for_recipient(3).is_none());
+
+        // same as encrypting to each key separately with the same nonce
+        pubkeys.iter().enumerate().for_each(|(j, pk)| {
+            let separate = encrypt_elgamal(pk, &nonce, &msg);
+            let part = encrypted.for_recipient(j).unwrap();
+            assert!(part.c1.equals(separate.c1) && part.c2.equals(separate.c2));
+        });
+
+        let shares1 = vec![
+            committee1[0].try_partial_decrypt_c1(&encrypted.c1, &vec![1, 3]).unwrap(),
+            committee1[2].try_partial_decrypt_c1(&encrypted.c1, &vec![1, 3]).unwrap(),
+        ];
+        assert!(decrypt(Ciphertext::try_from(encrypted.for_recipient(0).unwrap()).unwrap(), shares1, 2).equals(msg.clone()));
+
+        let shares2 = committee2.iter().map(|n| n.try_partial_decrypt_c1(&encrypted.c1, &vec![1, 2, 3]).unwrap()).collect();
+        assert!(decrypt(Ciphertext::try_from(encrypted.for_recipient(1).unwrap()).unwrap(), shares2, 3).equals(msg.clone()));
+
+        assert!(single.decrypt_elgamal(encrypted.for_recipient(2).unwrap()).equals(msg));
+    }
+}
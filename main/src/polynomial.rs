@@ -1,12 +1,15 @@
 use num_bigint::{RandBigInt, BigInt, Sign};
 use num_traits::{FromPrimitive};
-use babyjubjub_rs::{Fl, SUBORDER};
+use babyjubjub_rs::{Fl, Point, SUBORDER, FrBigIntConversion};
 use blake2::{Blake2b512, Digest};
 use ff::{Field, PrimeField};
-// use num_bigint::Sign;
-use std::ops::Mul;
 use serde::{Serialize, Deserialize};
 
+use crate::secret::wipe;
+use crate::msm::multi_scalar_mul;
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+
 
 // impl Serialize for Polynomial {
 //     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -20,14 +23,56 @@ use serde::{Serialize, Deserialize};
 //             }
 // }
 
-#[derive(Serialize, Deserialize)]
+/// Polynomial with coefficients in Fl, in order of increasing degree.
+/// Like a SecretScalar, it can't be cloned and doesn't implement Serialize: a struct holding one only serializes it if the field opts in with
+/// `#[serde(with = "crate::polynomial::expose")]`
 pub struct Polynomial {
+    coefficients: Vec<Fl>
+}
+
+// Serialized form of Polynomial
+#[derive(Serialize, Deserialize)]
+struct BigIntPolynomial {
     coefficients: Vec<BigInt>
 }
 
+/// Serializes a Polynomial as its coefficients' BigInt representations, so nodes stored before coefficients were kept in Fl can still be read
+pub(crate) mod expose {
+    use babyjubjub_rs::FrBigIntConversion;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use super::{Polynomial, BigIntPolynomial};
+
+    pub fn serialize<S: Serializer>(p: &Polynomial, serializer: S) -> Result<S::Ok, S::Error> {
+        BigIntPolynomial { coefficients: p.coefficients.iter().map(|c| c.to_bigint()).collect() }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Polynomial, D::Error> {
+        Ok(Polynomial::from_coeffs(BigIntPolynomial::deserialize(deserializer)?.coefficients))
+    }
+}
+
+/// Reduces x mod SUBORDER to a value in [0, SUBORDER)
+pub fn reduce(x: &BigInt) -> BigInt {
+    let r = x % &*SUBORDER;
+    if r.sign() == Sign::Minus { r + &*SUBORDER } else { r }
+}
+
+/// Converts x to an element of Fl, reducing it first if it's negative or not less than SUBORDER
+pub fn to_fl(x: &BigInt) -> Fl {
+    Fl::from_bigint(&reduce(x))
+}
+
+// Keygen polynomials are secret, so their coefficients are wiped when they're dropped
+impl Drop for Polynomial {
+    fn drop(&mut self) {
+        self.coefficients.iter_mut().for_each(wipe);
+    }
+}
+
 impl Polynomial {
+    /// Coefficients are reduced mod SUBORDER
     pub fn from_coeffs(coeffs: Vec<BigInt>) -> Polynomial {
-        Polynomial { coefficients: coeffs }
+        Polynomial { coefficients: coeffs.iter().map(to_fl).collect() }
     }
 
     // Creates a random polynomial with elements in Fl
@@ -36,7 +81,19 @@ impl Polynomial {
             |_| rand::thread_rng().gen_bigint_range(&BigInt::from_u8(0u8).unwrap() , &SUBORDER)
         ).collect::<Vec<BigInt>>();
 
-        Polynomial { coefficients: coeffs }
+        Polynomial::from_coeffs(coeffs)
+    }
+
+    /// Creates a random polynomial with elements in Fl, except the constant term which is `constant`
+    pub fn random_polynomial_fl_with_constant(degree: usize, constant: &Fl) -> Polynomial {
+        let mut p = Polynomial::random_polynomial_fl(degree);
+        p.coefficients[0] = *constant;
+        p
+    }
+
+    /// Creates a random polynomial with elements in Fl, except the constant term which is 0. Adding it to another polynomial changes every evaluation except the one at 0
+    pub fn random_polynomial_fl_zero_at_0(degree: usize) -> Polynomial {
+        Polynomial::random_polynomial_fl_with_constant(degree, &Fl::zero())
     }
 
     /// Genereates polynomial from a random seed by repeatedly hashing it to get eeach new coefficient
@@ -55,10 +112,39 @@ impl Polynomial {
         Polynomial::from_coeffs(coeffs)
     }
 
-    pub fn eval(&self, x: &BigInt) -> BigInt{
-        self.coefficients.iter().enumerate().map(
-            |(i, coeff)| x.pow(i as u32).mul(coeff)
-        ).sum::<BigInt>()
+    /// Evaluates the polynomial at x. The result is reduced mod SUBORDER
+    pub fn eval(&self, x: &BigInt) -> BigInt {
+        self.eval_fl(&to_fl(x)).to_bigint()
+    }
+
+    /// Evaluates the polynomial at x using Horner's method
+    pub fn eval_fl(&self, x: &Fl) -> Fl {
+        let mut acc = Fl::zero();
+        for coeff in self.coefficients.iter().rev() {
+            acc.mul_assign(x);
+            acc.add_assign(coeff);
+        }
+        acc
+    }
+
+    /// Coefficients in order of increasing degree
+    pub fn coefficients(&self) -> &Vec<Fl> {
+        &self.coefficients
+    }
+
+    /// Feldman commitments to the polynomial: each coefficient times the base point, in order of increasing degree.
+    /// Anyone holding these can check an evaluation of the polynomial without learning the coefficients. The coefficients are multiplied in constant time
+    pub fn commit(&self) -> Vec<Point> {
+        self.coefficients.iter().map(mul_b8_ct).collect()
+    }
+
+    /// Pedersen commitments to the polynomial using `blinding` as the blinding polynomial: a_k*B8 + b_k*H for each pair of coefficients.
+    /// Unlike `commit`, these reveal nothing about the coefficients, even to an unbounded adversary
+    pub fn pedersen_commit(&self, blinding: &Polynomial, h: &Point) -> Vec<Point> {
+        assert_eq!(self.deg(), blinding.deg(), "blinding polynomial must be the same degree as the polynomial being committed to");
+        self.coefficients.iter().zip(blinding.coefficients.iter()).map(
+            |(a, b)| mul_b8_ct(a).add(&mul_scalar_ct(h, b))
+        ).collect()
     }
 
     /// Degree of the polynomial
@@ -69,17 +155,47 @@ impl Polynomial {
     /// adds to another polynomial of same degree
     pub fn add_same_deg(&self, other_polynomial: &Polynomial) -> Polynomial {
         assert_eq!(self.deg(), other_polynomial.deg(), 
-            "Error adding polynomials with coefficients {:?} and {:?} Currently, adding polynomials is only supported for polynomials of the same degree",
-            self.coefficients, other_polynomial.coefficients
+            "Error adding polynomials of degrees {} and {}. Currently, adding polynomials is only supported for polynomials of the same degree",
+            self.deg(), other_polynomial.deg()
+        );
+        self.add(other_polynomial)
+    }
+
+    /// Adds to another polynomial of any degree. The result has as many coefficients as the longer of the two
+    pub fn add(&self, other_polynomial: &Polynomial) -> Polynomial {
+        let (longer, shorter) = if self.coefficients.len() >= other_polynomial.coefficients.len() {
+            (self, other_polynomial)
+        } else {
+            (other_polynomial, self)
+        };
+        let mut new_coefs = longer.coefficients.clone();
+        new_coefs.iter_mut().zip(shorter.coefficients.iter()).for_each(
+            |(a, b)| a.add_assign(b)
         );
-        let new_coefs = self.coefficients.iter().zip(
-            other_polynomial.coefficients.clone()
-        ).map(
-            |(a,b)| a + b
-        ).collect();
         Polynomial { coefficients: new_coefs }
     }
 
+    /// Multiplies by another polynomial of any degree. The result's degree is the sum of the two degrees
+    pub fn mul(&self, other_polynomial: &Polynomial) -> Polynomial {
+        let mut new_coefs = vec![Fl::zero(); self.deg() + other_polynomial.deg() + 1];
+        for (i, a) in self.coefficients.iter().enumerate() {
+            for (j, b) in other_polynomial.coefficients.iter().enumerate() {
+                let mut term = a.clone();
+                term.mul_assign(b);
+                new_coefs[i + j].add_assign(&term);
+            }
+        }
+        Polynomial { coefficients: new_coefs }
+    }
+
+}
+
+/// Evaluates a committed polynomial "in the exponent" at x: given commitments C_k to the coefficients, returns ∑ x^k * C_k
+pub fn eval_commitments(commitments: &Vec<Point>, x: usize) -> Point {
+    let x = BigInt::from_usize(x).unwrap();
+    let powers: Vec<BigInt> = (0..commitments.len()).map(|k| x.pow(k as u32)).collect();
+    // one power per commitment, so this can't fail
+    multi_scalar_mul(commitments, &powers).unwrap()
 }
 
 // NOTE: look more into security of a user bieng able to ask "decrypt this with nodes i1, i2, and i3", then being able to asl "decrypt this with nodes i4, i5, and i6". Does this reveal any information about the private key? I would assume not because this is standard, but seems strange and still worth more detailed analysis.
@@ -137,6 +253,14 @@ mod tests {
         assert!(p3.coefficients.len() == 4);
     }
 
+    #[test]
+    fn test_random_polynomial_zero_at_0() {
+        let p = Polynomial::random_polynomial_fl_zero_at_0(3);
+        assert!(p.deg() == 3);
+        assert!(p.eval(&0.to_bigint().unwrap()) == 0.to_bigint().unwrap());
+        assert!(p.eval(&1.to_bigint().unwrap()) != 0.to_bigint().unwrap());
+    }
+
     #[test]
     fn test_lagrange_basis_at_0_2of2() {
         // TODO: refactor this to be more concise
@@ -273,11 +397,56 @@ mod tests {
         );
         let p3 = p1.add_same_deg(&p2);
         assert!(
-            (p3.coefficients[0] == 109.to_bigint().unwrap()) &&
-            (p3.coefficients[1] == 70.to_bigint().unwrap()) &&
-            (p3.coefficients[2] == 5.to_bigint().unwrap()) &&
-            (p3.coefficients[3] == 7.to_bigint().unwrap())
+            (p3.coefficients[0] == Fl::from_str("109").unwrap()) &&
+            (p3.coefficients[1] == Fl::from_str("70").unwrap()) &&
+            (p3.coefficients[2] == Fl::from_str("5").unwrap()) &&
+            (p3.coefficients[3] == Fl::from_str("7").unwrap())
         )
     }
 
+    #[test]
+    fn test_add_and_mul_different_degrees() {
+        // (1 + 2x) + (3 + 4x + 5x^2) = 4 + 6x + 5x^2
+        let p1 = Polynomial::from_coeffs(vec![1.to_bigint().unwrap(), 2.to_bigint().unwrap()]);
+        let p2 = Polynomial::from_coeffs(vec![3.to_bigint().unwrap(), 4.to_bigint().unwrap(), 5.to_bigint().unwrap()]);
+        let sum = p1.add(&p2);
+        assert!(sum.deg() == 2);
+        assert!(sum.eval(&2.to_bigint().unwrap()) == 36.to_bigint().unwrap());
+        assert!(p2.add(&p1).eval(&2.to_bigint().unwrap()) == 36.to_bigint().unwrap());
+
+        // (1 + 2x)(3 + 4x + 5x^2) = 3 + 10x + 13x^2 + 10x^3
+        let product = p1.mul(&p2);
+        assert!(product.deg() == 3);
+        assert!(product.eval(&2.to_bigint().unwrap()) == 135.to_bigint().unwrap());
+    }
+
+    // Coefficients and evaluations should always be reduced mod SUBORDER, no matter how large the inputs are
+    #[test]
+    fn test_eval_is_reduced() {
+        let big = &*SUBORDER + 5.to_bigint().unwrap();
+        let p = Polynomial::from_coeffs(vec![big.clone(), big.clone(), (-1).to_bigint().unwrap()]);
+        // 5 + 5x - x^2 at x = SUBORDER + 2 is 5 + 10 - 4
+        assert!(p.eval(&(&*SUBORDER + 2.to_bigint().unwrap())) == 11.to_bigint().unwrap());
+        let r = Polynomial::random_polynomial_fl(4);
+        (1..100).for_each(|x| assert!(r.eval(&x.to_bigint().unwrap()) < *SUBORDER));
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct Holder {
+        #[serde(with = "expose")]
+        polynomial: Polynomial,
+    }
+
+    // Polynomials should serialize the same way as when coefficients were stored as BigInts, and unreduced stored coefficients should still be readable
+    #[test]
+    fn test_serialization_compatible() {
+        let old_format = serde_json::json!({ "polynomial": { "coefficients": [ 123.to_bigint().unwrap(), (&*SUBORDER + 1.to_bigint().unwrap()) ] } });
+        let h: Holder = serde_json::from_value(old_format).unwrap();
+        assert!(h.polynomial.coefficients[0] == Fl::from_str("123").unwrap());
+        assert!(h.polynomial.coefficients[1] == Fl::one());
+
+        let serialized = serde_json::to_value(&h).unwrap();
+        assert_eq!(serialized, serde_json::json!({ "polynomial": { "coefficients": [ 123.to_bigint().unwrap(), 1.to_bigint().unwrap() ] } }));
+    }
+
 }
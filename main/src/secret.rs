@@ -0,0 +1,133 @@
+/* Secret scalars, which are overwritten with zero when dropped and print as REDACTED.
+   They don't implement Serialize, so a struct holding one only serializes it if the field opts in with `#[serde(with = "crate::secret::expose")]`.
+   Keygen polynomials work the same way, with `crate::polynomial::expose` */
+use std::fmt;
+use std::sync::atomic::{compiler_fence, Ordering};
+use num_bigint::BigInt;
+use babyjubjub_rs::{Fl, Point, FrBigIntConversion};
+use ff::{Field, PrimeField};
+
+use crate::polynomial::to_fl;
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+
+/// A secret element of Fl, such as a keyshare
+#[derive(Clone)]
+pub struct SecretScalar(Fl);
+
+impl SecretScalar {
+    pub fn new(x: Fl) -> SecretScalar {
+        SecretScalar(x)
+    }
+
+    /// `x` reduced mod SUBORDER
+    pub fn from_bigint(x: &BigInt) -> SecretScalar {
+        SecretScalar(to_fl(x))
+    }
+
+    /// Parses a decimal string straight into Fl, reducing it mod SUBORDER, so no BigInt copy of the secret is left behind. Returns None if `s` isn't a decimal integer
+    pub fn from_decimal_str(s: &str) -> Option<SecretScalar> {
+        Fl::from_str(s).map(SecretScalar)
+    }
+
+    /// The sum of `values` mod SUBORDER, added up in Fl rather than as a BigInt
+    pub(crate) fn sum<'a>(values: impl Iterator<Item = &'a BigInt>) -> SecretScalar {
+        let mut sum = Fl::zero();
+        for v in values {
+            let mut x = to_fl(v);
+            sum.add_assign(&x);
+            wipe(&mut x);
+        }
+        let result = SecretScalar(sum);
+        wipe(&mut sum);
+        result
+    }
+
+    pub fn expose_secret(&self) -> &Fl {
+        &self.0
+    }
+
+    /// `self`*`p`, in constant time (see `constant_time::mul_scalar_ct`)
+    pub fn mul_point(&self, p: &Point) -> Point {
+        mul_scalar_ct(p, &self.0)
+    }
+
+    /// `self`*B8, in constant time using the B8 table (see `fixed_base::mul_b8_ct`)
+    pub fn mul_b8(&self) -> Point {
+        mul_b8_ct(&self.0)
+    }
+
+    /// The secret as a BigInt. Unlike the SecretScalar, the BigInt isn't zeroized when dropped, so it should be kept for as short a time as possible
+    pub fn expose_bigint(&self) -> BigInt {
+        self.0.to_bigint()
+    }
+}
+
+impl Drop for SecretScalar {
+    fn drop(&mut self) {
+        wipe(&mut self.0);
+    }
+}
+
+impl fmt::Debug for SecretScalar {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretScalar(REDACTED)")
+    }
+}
+
+/// Overwrites `x` with zero in a way the compiler won't optimize out
+pub(crate) fn wipe(x: &mut Fl) {
+    // Safety: x is a valid, aligned reference, and Fl::zero() is a valid Fl
+    unsafe { std::ptr::write_volatile(x, Fl::zero()) };
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// Serializes a SecretScalar as its BigInt, the same way a BigInt field would be. Use with `#[serde(with = "crate::secret::expose")]`
+pub mod expose {
+    use num_bigint::BigInt;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+    use super::SecretScalar;
+
+    pub fn serialize<S: Serializer>(s: &SecretScalar, serializer: S) -> Result<S::Ok, S::Error> {
+        s.expose_bigint().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SecretScalar, D::Error> {
+        Ok(SecretScalar::from_bigint(&BigInt::deserialize(deserializer)?))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Serialize, Deserialize};
+
+    #[derive(Serialize, Deserialize)]
+    struct Holder {
+        #[serde(with = "expose")]
+        secret: SecretScalar,
+    }
+
+    #[test]
+    fn test_secret_scalar() {
+        let s = SecretScalar::from_bigint(&BigInt::from(123456789u32));
+        assert_eq!(format!("{:?}", s), "SecretScalar(REDACTED)");
+        assert_eq!(s.expose_bigint(), BigInt::from(123456789u32));
+
+        // serializes like a BigInt when opted in
+        let json = serde_json::to_string(&Holder { secret: s.clone() }).unwrap();
+        assert_eq!(json, serde_json::to_string(&serde_json::json!({ "secret": BigInt::from(123456789u32) })).unwrap());
+        let back: Holder = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.secret.expose_bigint(), s.expose_bigint());
+
+        let mut x = *s.expose_secret();
+        wipe(&mut x);
+        assert!(x.is_zero());
+
+        assert_eq!(SecretScalar::from_decimal_str("123456789").unwrap().expose_bigint(), BigInt::from(123456789u32));
+        assert!(SecretScalar::from_decimal_str("0x1234").is_none());
+        let summed = SecretScalar::sum([BigInt::from(5u8), BigInt::from(-2)].iter());
+        assert_eq!(summed.expose_bigint(), BigInt::from(3u8));
+    }
+}
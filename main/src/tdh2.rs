@@ -0,0 +1,256 @@
+/*
+Chosen-ciphertext secure threshold encryption (Shoup and Gennaro, "Securing Threshold Cryptosystems against Chosen Ciphertext Attack", TDH2)
+
+Plain ElGamal is malleable: given an encryption (C1, C2) of m, (C1, C2 + X) is an encryption of m + X, so anyone who can get a forged ciphertext
+decrypted can recover m. Here, the encrypter also proves it knows the nonce r behind C1, and the proof is bound to C2 and a label.
+A ciphertext can't be changed without breaking the proof, and nodes refuse to partially decrypt a ciphertext whose proof doesn't verify.
+
+Encryption of point m with label L, where G is a second generator nobody knows the discrete log of with respect to B8:
+1. Pick random r and s. C1 = r*B8, C2 = m + r*PK, C1' = r*G
+2. W = s*B8, W' = s*G
+3. e = H(C2, L, C1, W, C1', W') and f = s + r*e
+
+Anyone can verify the ciphertext by checking e = H(C2, L, C1, f*B8 - e*C1, C1', f*G - e*C1'). This shows C1 and C1' have the same discrete log r,
+which only someone who chose r (and so already knows m) can do. The label is whatever context the ciphertext should only be decrypted in,
+e.g. a user ID; changing it invalidates the proof.
+`Node::partial_decrypt`, `raw_partial_decrypt` and `partial_decrypt_with_proof` take a `Tdh2Ciphertext`, check its proof and give the usual (raw or DLEQ-proven) decryption shares of C1.
+Their `_c1` versions decrypt a bare C1 with no such check
+
+A `LabeledCiphertext` is a TDH2 ciphertext whose label is a `CiphertextLabel` saying who the ciphertext is for, what it may be decrypted for and until when,
+so node operators can see what they are being asked to decrypt and refuse requests that are against their policy
+*/
+use std::sync::OnceLock;
+use num_bigint::{BigInt, RandBigInt, Sign};
+use babyjubjub_rs::{Point, ElGamalEncryption, B8, SUBORDER};
+use blake2::{Blake2b512, Digest};
+use serde::{Serialize, Deserialize};
+
+use crate::{Error, Ciphertext, PublicKey, VerifiableDecryptionShare, decrypt_verified};
+use crate::polynomial::{reduce, to_fl};
+use crate::constant_time::mul_scalar_ct;
+use crate::fixed_base::mul_b8_ct;
+
+const TDH2_G_DOMAIN: &str = "babyjubjub-elgamal TDH2 generator G";
+const TDH2_CHALLENGE_DOMAIN: &[u8] = b"babyjubjub-elgamal TDH2 challenge";
+
+/// Second generator for TDH2 ciphertexts. It is hashed to the curve so that nobody knows its discrete log with respect to B8
+pub fn tdh2_generator() -> Point {
+    static G: OnceLock<Point> = OnceLock::new();
+    G.get_or_init(
+        || Point::hash_to_curve_bls(TDH2_G_DOMAIN.as_bytes()).expect("failed to hash the TDH2 generator to the curve")
+    ).clone()
+}
+
+/// An ElGamal encryption (`c1`, `c2`) along with a proof binding it to `label`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tdh2Ciphertext {
+    pub c1: Point,
+    pub c2: Point,
+    /// r*G, where r is the nonce behind c1
+    pub c1_bar: Point,
+    pub label: Vec<u8>,
+    pub e: BigInt,
+    pub f: BigInt,
+}
+
+impl Tdh2Ciphertext {
+    /// Checks the proof that c1 and c1_bar have the same discrete log, bound to c2 and the label
+    pub fn verify(&self) -> bool {
+        if !self.c1.on_curve() || !self.c1.in_subgroup() || !self.c1_bar.on_curve() || !self.c1_bar.in_subgroup() || !self.c2.on_curve() {
+            return false;
+        }
+        let w = B8.mul_scalar(&self.f).add(&self.c1.mul_scalar(&self.e).neg());
+        let w_bar = tdh2_generator().mul_scalar(&self.f).add(&self.c1_bar.mul_scalar(&self.e).neg());
+        challenge(&self.c2, &self.label, &self.c1, &w, &self.c1_bar, &w_bar) == self.e
+    }
+
+    /// The ciphertext as a plain ElGamal encryption, without the proof
+    pub fn elgamal(&self) -> ElGamalEncryption {
+        ElGamalEncryption { c1: self.c1.clone(), c2: self.c2.clone() }
+    }
+}
+
+/// What a ciphertext may be decrypted for. It is bound to the ciphertext as its TDH2 label
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CiphertextLabel {
+    pub user_id: String,
+    pub purpose: String,
+    /// Unix time in seconds after which the ciphertext should no longer be decrypted
+    pub expiry: u64,
+}
+
+impl CiphertextLabel {
+    /// Canonical encoding used as the TDH2 label: the length of `user_id` as a big-endian u32, then `user_id`,
+    /// then the same for `purpose`, then `expiry` as a big-endian u64
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for field in [&self.user_id, &self.purpose] {
+            bytes.extend_from_slice(&(field.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(field.as_bytes());
+        }
+        bytes.extend_from_slice(&self.expiry.to_be_bytes());
+        bytes
+    }
+
+    /// Decodes the output of `to_bytes`, rejecting anything with trailing bytes or fields that aren't UTF-8
+    pub fn from_bytes(bytes: &[u8]) -> Result<CiphertextLabel, Error> {
+        let invalid = |msg: &str| Error::InvalidEncoding(format!("label {}", msg));
+        let mut rest = bytes;
+        let mut fields = vec![];
+        for _ in 0..2 {
+            if rest.len() < 4 {
+                return Err(invalid("is too short"));
+            }
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            if rest.len() - 4 < len {
+                return Err(invalid("is too short"));
+            }
+            fields.push(String::from_utf8(rest[4..4 + len].to_vec()).map_err(|_| invalid("field is not UTF-8"))?);
+            rest = &rest[4 + len..];
+        }
+        if rest.len() != 8 {
+            return Err(invalid("has the wrong length"));
+        }
+        let purpose = fields.pop().unwrap();
+        let user_id = fields.pop().unwrap();
+        Ok(CiphertextLabel { user_id, purpose, expiry: u64::from_be_bytes(rest.try_into().unwrap()) })
+    }
+
+    /// Whether the label has expired at Unix time `now`
+    pub fn expired(&self, now: u64) -> bool {
+        now > self.expiry
+    }
+}
+
+/// A TDH2 ciphertext along with the label it is bound to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabeledCiphertext {
+    pub label: CiphertextLabel,
+    pub ciphertext: Tdh2Ciphertext,
+}
+
+impl LabeledCiphertext {
+    /// Checks the ciphertext's proof and that it is bound to `label`
+    pub fn verify(&self) -> bool {
+        self.ciphertext.label == self.label.to_bytes() && self.ciphertext.verify()
+    }
+}
+
+/// Encrypts `msg` to `pubkey`, bound to `label`
+pub fn encrypt_labeled(pubkey: &PublicKey, msg: &Point, label: &CiphertextLabel) -> LabeledCiphertext {
+    LabeledCiphertext {
+        label: label.clone(),
+        ciphertext: encrypt_tdh2(pubkey, msg, &label.to_bytes())
+    }
+}
+
+/// Encrypts `msg` to `pubkey` so it can only be decrypted with `label`
+pub fn encrypt_tdh2(pubkey: &PublicKey, msg: &Point, label: &[u8]) -> Tdh2Ciphertext {
+    let one = BigInt::from(1u8);
+    let r = rand::thread_rng().gen_bigint_range(&one, &SUBORDER);
+    let s = rand::thread_rng().gen_bigint_range(&one, &SUBORDER);
+    let g = tdh2_generator();
+
+    let (r_fl, s_fl) = (to_fl(&r), to_fl(&s));
+    let c1 = mul_b8_ct(&r_fl);
+    let c2 = msg.add(&mul_scalar_ct(pubkey, &r_fl));
+    let c1_bar = mul_scalar_ct(&g, &r_fl);
+    let e = challenge(&c2, label, &c1, &mul_b8_ct(&s_fl), &c1_bar, &mul_scalar_ct(&g, &s_fl));
+    let f = reduce(&(s + r * &e));
+    Tdh2Ciphertext { c1, c2, c1_bar, label: label.to_vec(), e, f }
+}
+
+/// Checks the ciphertext, then decrypts it using decryption shares from `num_shares_needed` nodes after checking every share's proof
+pub fn decrypt_tdh2(encrypted: &Tdh2Ciphertext, shares: Vec<VerifiableDecryptionShare>, verification_keys: &Vec<Point>, num_shares_needed: u64) -> Result<Point, Error> {
+    if !encrypted.verify() {
+        return Err(Error::InvalidCiphertext);
+    }
+    Ok(decrypt_verified(Ciphertext::try_from(encrypted.elgamal())?, shares, verification_keys, num_shares_needed)?)
+}
+
+// H(domain || C2 || len(L) || L || C1 || W || C1' || W') mod SUBORDER
+fn challenge(c2: &Point, label: &[u8], c1: &Point, w: &Point, c1_bar: &Point, w_bar: &Point) -> BigInt {
+    let mut h = Blake2b512::new();
+    h.update(TDH2_CHALLENGE_DOMAIN);
+    h.update(c2.compress());
+    h.update((label.len() as u64).to_be_bytes());
+    h.update(label);
+    h.update(c1.compress());
+    h.update(w.compress());
+    h.update(c1_bar.compress());
+    h.update(w_bar.compress());
+    BigInt::from_bytes_be(Sign::Plus, &h.finalize()) % &*SUBORDER
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::calculate_pubkey;
+    use crate::tests::init_keyshared_nodes;
+
+    #[test]
+    fn test_tdh2_decrypt() {
+        let nodes = init_keyshared_nodes(2, 3);
+        let pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let verification_keys: Vec<Point> = nodes.iter().map(|n| n.verification_key().unwrap()).collect();
+        let msg = B8.mul_scalar(&BigInt::from(123456789u32));
+
+        let encrypted = encrypt_tdh2(&pubkey, &msg, b"user 1");
+        assert!(encrypted.verify());
+
+        let decrypting = vec![2, 3];
+        let shares = decrypting.iter().map(|i| nodes[*i as usize - 1].partial_decrypt_with_proof(&encrypted, &decrypting)).collect();
+        assert!(decrypt_tdh2(&encrypted, shares, &verification_keys, 2).unwrap().equals(msg));
+
+        let ciphertext = Ciphertext::try_from(encrypted.elgamal()).unwrap();
+        let shares = decrypting.iter().map(|i| nodes[*i as usize - 1].partial_decrypt(&encrypted, &decrypting)).collect();
+        assert!(crate::decrypt(ciphertext.clone(), shares, 2).equals(msg.clone()));
+        let raw_shares: Vec<_> = nodes[..2].iter().map(|n| n.raw_partial_decrypt(&encrypted)).collect();
+        assert!(crate::decrypt_raw(ciphertext, &raw_shares, 2).equals(msg));
+    }
+
+    // Mauling the ciphertext or changing its label should make the nodes refuse to decrypt it
+    #[test]
+    fn test_tdh2_rejects_forgeries() {
+        let nodes = init_keyshared_nodes(2, 2);
+        let pubkey = calculate_pubkey(nodes.iter().map(|n| n.pubkey_share()).collect()).unwrap();
+        let encrypted = encrypt_tdh2(&pubkey, &B8.mul_scalar(&BigInt::from(5u8)), b"user 1");
+
+        let mut mauled = encrypted.clone();
+        mauled.c2 = mauled.c2.add(&B8);
+        assert!(matches!(nodes[0].try_partial_decrypt_with_proof(&mauled, &vec![1, 2]), Err(Error::InvalidCiphertext)));
+        assert!(matches!(nodes[0].try_partial_decrypt(&mauled, &vec![1, 2]), Err(Error::InvalidCiphertext)));
+        assert!(matches!(nodes[0].try_raw_partial_decrypt(&mauled), Err(Error::InvalidCiphertext)));
+
+        let mut relabeled = encrypted.clone();
+        relabeled.label = b"user 2".to_vec();
+        assert!(matches!(nodes[0].try_partial_decrypt_with_proof(&relabeled, &vec![1, 2]), Err(Error::InvalidCiphertext)));
+
+        let mut new_nonce = encrypted.clone();
+        new_nonce.c1 = new_nonce.c1.add(&B8);
+        assert!(matches!(nodes[0].try_partial_decrypt_with_proof(&new_nonce, &vec![1, 2]), Err(Error::InvalidCiphertext)));
+    }
+
+    #[test]
+    fn test_labeled_ciphertext() {
+        let pubkey = PublicKey::new(B8.mul_scalar(&BigInt::from(1234u32))).unwrap();
+        let label = CiphertextLabel { user_id: "user 1".to_string(), purpose: "court order".to_string(), expiry: 2000000000 };
+        let encrypted = encrypt_labeled(&pubkey, &B8, &label);
+        assert!(encrypted.verify());
+        assert!(!encrypted.label.expired(1700000000));
+        assert!(encrypted.label.expired(2000000001));
+
+        // the label can't be swapped out, even by moving characters from one field to another
+        let mut other_user = encrypted.clone();
+        other_user.label.user_id = "user 2".to_string();
+        assert!(!other_user.verify());
+        let mut shifted = encrypted.clone();
+        shifted.label.user_id = "user 1court".to_string();
+        shifted.label.purpose = " order".to_string();
+        assert!(!shifted.verify());
+        let mut extended = encrypted.clone();
+        extended.label.expiry += 1;
+        assert!(!extended.verify());
+    }
+}
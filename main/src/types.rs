@@ -0,0 +1,227 @@
+/* Distinct types for the points passed between nodes and clients, so that e.g. a pubkey share can't be used where a decryption share is expected.
+   Every one of them is checked when it's constructed or deserialized, so functions taking them don't have to */
+use std::ops::Deref;
+use num_bigint::BigInt;
+use babyjubjub_rs::{Point, ElGamalEncryption};
+use serde::{Serialize, Deserialize};
+
+use crate::{Error, check_point};
+use crate::fixed_base::encrypt_elgamal;
+
+/// Joint public key of a set of nodes, which messages are encrypted to. Serializes as a `Point`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Point", into = "Point")]
+pub struct PublicKey(Point);
+
+impl PublicKey {
+    /// Checks `p` is on the curve and in the subgroup
+    pub fn new(p: Point) -> Result<PublicKey, Error> {
+        check_point(&p)?;
+        Ok(PublicKey(p))
+    }
+    pub(crate) fn new_unchecked(p: Point) -> PublicKey {
+        PublicKey(p)
+    }
+    pub fn into_point(self) -> Point {
+        self.0
+    }
+}
+
+impl Deref for PublicKey {
+    type Target = Point;
+    fn deref(&self) -> &Point {
+        &self.0
+    }
+}
+
+impl TryFrom<Point> for PublicKey {
+    type Error = Error;
+    fn try_from(p: Point) -> Result<PublicKey, Error> {
+        PublicKey::new(p)
+    }
+}
+
+impl From<PublicKey> for Point {
+    fn from(pk: PublicKey) -> Point {
+        pk.0
+    }
+}
+
+/// A node's share A_i(0)*B8 of the joint public key. Serializes as a `Point`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "Point", into = "Point")]
+pub struct PubkeyShare(Point);
+
+impl PubkeyShare {
+    /// Checks `p` is on the curve and in the subgroup
+    pub fn new(p: Point) -> Result<PubkeyShare, Error> {
+        check_point(&p)?;
+        Ok(PubkeyShare(p))
+    }
+    pub(crate) fn new_unchecked(p: Point) -> PubkeyShare {
+        PubkeyShare(p)
+    }
+    pub fn into_point(self) -> Point {
+        self.0
+    }
+}
+
+impl Deref for PubkeyShare {
+    type Target = Point;
+    fn deref(&self) -> &Point {
+        &self.0
+    }
+}
+
+impl TryFrom<Point> for PubkeyShare {
+    type Error = Error;
+    fn try_from(p: Point) -> Result<PubkeyShare, Error> {
+        PubkeyShare::new(p)
+    }
+}
+
+impl From<PubkeyShare> for Point {
+    fn from(pks: PubkeyShare) -> Point {
+        pks.0
+    }
+}
+
+/// Node `node_idx`'s decryption share L_i(0)*A(i)*C1, as returned by `Node::partial_decrypt`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedDecryptionShare", into = "UncheckedDecryptionShare")]
+pub struct DecryptionShare {
+    node_idx: usize,
+    point: Point,
+}
+
+// Serialized form of DecryptionShare
+#[derive(Serialize, Deserialize)]
+struct UncheckedDecryptionShare {
+    node_idx: usize,
+    point: Point,
+}
+
+impl DecryptionShare {
+    /// Checks `node_idx` is a valid node index and `point` is on the curve and in the subgroup
+    pub fn new(node_idx: usize, point: Point) -> Result<DecryptionShare, Error> {
+        if node_idx == 0 {
+            return Err(Error::InvalidDecryptingNodes("node index must be greater than 0".to_string()));
+        }
+        check_point(&point)?;
+        Ok(DecryptionShare { node_idx, point })
+    }
+    pub(crate) fn new_unchecked(node_idx: usize, point: Point) -> DecryptionShare {
+        DecryptionShare { node_idx, point }
+    }
+    pub fn node_idx(&self) -> usize {
+        self.node_idx
+    }
+    pub fn point(&self) -> &Point {
+        &self.point
+    }
+}
+
+impl TryFrom<UncheckedDecryptionShare> for DecryptionShare {
+    type Error = Error;
+    fn try_from(s: UncheckedDecryptionShare) -> Result<DecryptionShare, Error> {
+        DecryptionShare::new(s.node_idx, s.point)
+    }
+}
+
+impl From<DecryptionShare> for UncheckedDecryptionShare {
+    fn from(s: DecryptionShare) -> UncheckedDecryptionShare {
+        UncheckedDecryptionShare { node_idx: s.node_idx, point: s.point }
+    }
+}
+
+/// An ElGamal encryption (C1, C2). Serializes the same way as an `ElGamalEncryption`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "ElGamalEncryption", into = "ElGamalEncryption")]
+pub struct Ciphertext {
+    c1: Point,
+    c2: Point,
+}
+
+impl Ciphertext {
+    /// Checks `c1` is on the curve and in the subgroup, and `c2` is on the curve.
+    /// `c2` isn't required to be in the subgroup, since encoded messages may not be
+    pub fn new(c1: Point, c2: Point) -> Result<Ciphertext, Error> {
+        check_point(&c1)?;
+        if !c2.on_curve() {
+            return Err(Error::NotOnCurve);
+        }
+        Ok(Ciphertext { c1, c2 })
+    }
+    pub(crate) fn new_unchecked(c1: Point, c2: Point) -> Ciphertext {
+        Ciphertext { c1, c2 }
+    }
+    pub fn c1(&self) -> &Point {
+        &self.c1
+    }
+    pub fn c2(&self) -> &Point {
+        &self.c2
+    }
+}
+
+impl TryFrom<ElGamalEncryption> for Ciphertext {
+    type Error = Error;
+    fn try_from(e: ElGamalEncryption) -> Result<Ciphertext, Error> {
+        Ciphertext::new(e.c1, e.c2)
+    }
+}
+
+impl From<Ciphertext> for ElGamalEncryption {
+    fn from(c: Ciphertext) -> ElGamalEncryption {
+        ElGamalEncryption { c1: c.c1, c2: c.c2 }
+    }
+}
+
+/// Encrypts `msg` to `pubkey` using `nonce`
+pub fn encrypt(pubkey: &PublicKey, nonce: &BigInt, msg: &Point) -> Ciphertext {
+    let e = encrypt_elgamal(pubkey, nonce, msg);
+    Ciphertext { c1: e.c1, c2: e.c2 }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use babyjubjub_rs::{Fr, B8};
+    use ff::Field;
+
+    #[test]
+    fn test_invalid_points_are_rejected() {
+        let off_curve = Point { x: Fr::one(), y: Fr::one() };
+        assert!(matches!(PublicKey::new(off_curve.clone()), Err(Error::NotOnCurve)));
+        assert!(matches!(PubkeyShare::new(off_curve.clone()), Err(Error::NotOnCurve)));
+        assert!(matches!(DecryptionShare::new(1, off_curve.clone()), Err(Error::NotOnCurve)));
+        assert!(matches!(DecryptionShare::new(0, B8.clone()), Err(Error::InvalidDecryptingNodes(_))));
+        assert!(matches!(Ciphertext::new(off_curve.clone(), B8.clone()), Err(Error::NotOnCurve)));
+        assert!(matches!(Ciphertext::new(B8.clone(), off_curve.clone()), Err(Error::NotOnCurve)));
+
+        // and the same checks happen when deserializing
+        let off_curve_json = serde_json::to_value(&off_curve).unwrap();
+        assert!(serde_json::from_value::<PublicKey>(off_curve_json.clone()).is_err());
+        assert!(serde_json::from_value::<DecryptionShare>(serde_json::json!({ "node_idx": 1, "point": off_curve_json })).is_err());
+    }
+
+    #[test]
+    fn test_serialization() {
+        let pubkey = PublicKey::new(B8.clone()).unwrap();
+        let json = serde_json::to_value(&pubkey).unwrap();
+        assert_eq!(json, serde_json::to_value(&*B8).unwrap());
+        assert!(serde_json::from_value::<PublicKey>(json).unwrap().equals(B8.clone()));
+
+        let encrypted = encrypt(&pubkey, &BigInt::from(7654321u32), &B8);
+        let json = serde_json::to_value(&encrypted).unwrap();
+        let as_elgamal: ElGamalEncryption = serde_json::from_value(json.clone()).unwrap();
+        assert!(as_elgamal.c1.equals(encrypted.c1().clone()) && as_elgamal.c2.equals(encrypted.c2().clone()));
+        let back: Ciphertext = serde_json::from_value(json).unwrap();
+        assert!(back.c2().equals(encrypted.c2().clone()));
+
+        let share = DecryptionShare::new(2, B8.clone()).unwrap();
+        let back: DecryptionShare = serde_json::from_str(&serde_json::to_string(&share).unwrap()).unwrap();
+        assert_eq!(back.node_idx(), 2);
+        assert!(back.point().equals(B8.clone()));
+    }
+}
@@ -9,6 +9,9 @@ use serde::{Serialize, Deserialize};
 // JS Client
 use wasm_bindgen::prelude::*;
 use blake2::{Blake2b512, Digest};
+use zeroize::Zeroizing;
+use babyjubjub_elgamal::dleq::BatchVerify;
+use babyjubjub_elgamal::constant_time::mul_scalar_ct;
 
 type Result<T> = std::result::Result<T, JsError>;
 
@@ -44,7 +47,17 @@ pub struct Client {}
 #[derive(Serialize, Deserialize)]
 pub struct Step1Result {
     masked: Point,
-    unmasker_keepthissecret: String,
+    /// Zeroized when dropped. It's serialized because the caller needs it for step2, but should never be sent anywhere
+    #[serde(serialize_with = "expose_unmasker", deserialize_with = "read_unmasker")]
+    unmasker_keepthissecret: Zeroizing<String>,
+}
+
+fn expose_unmasker<S: serde::Serializer>(unmasker: &Zeroizing<String>, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(unmasker)
+}
+
+fn read_unmasker<'de, D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Zeroizing<String>, D::Error> {
+    Ok(Zeroizing::new(String::deserialize(deserializer)?))
 }
 
 pub fn hash(input: Vec<u8>) -> Vec<u8> {
@@ -66,15 +79,37 @@ pub fn step1(plaintext: &str) -> std::result::Result<Step1Result, Error> {
         let rnd_fl_inv = rnd_fl.inverse().unwrap();
 
        Ok(Step1Result {
-            masked: hashed.mul_scalar(&rnd_bi),
-            unmasker_keepthissecret: rnd_fl_inv.to_dec_string(),
+            masked: mul_scalar_ct(hashed, &rnd_fl),
+            unmasker_keepthissecret: Zeroizing::new(rnd_fl_inv.to_dec_string()),
         })
 }
 
 pub fn step2(unmasker: String, server_response: DLEQProof) -> std::result::Result<Vec<u8>, Error> {
+    let unmasker = Zeroizing::new(unmasker);
     if !server_response.verify() { return Err(Error::FailedToVerifyProof("failed to verify VOPRF ZKP".to_string()).into()); }
-    let unmasker_bi = BigInt::from_str(unmasker.as_str()).map_err(|e|Error::InvalidInput(e.to_string()))?;
-        let unmasked = server_response.xB.mul_scalar(&unmasker_bi);
+    unmask(&unmasker, &server_response.xB)
+}
+
+/// Same as `step2` for several OPRF outputs at once. `unmaskers[i]` is for `server_responses[i]`. All the proofs are checked before anything is unmasked
+pub fn step2_batch(unmaskers: Vec<String>, server_responses: Vec<DLEQProof>) -> std::result::Result<Vec<Vec<u8>>, Error> {
+    let unmaskers: Vec<Zeroizing<String>> = unmaskers.into_iter().map(Zeroizing::new).collect();
+    if unmaskers.len() != server_responses.len() {
+        return Err(Error::InvalidInput(format!("{} unmaskers for {} server responses", unmaskers.len(), server_responses.len())));
+    }
+    DLEQProof::batch_verify(&server_responses)
+        .map_err(|i| Error::FailedToVerifyProof(format!("failed to verify VOPRF ZKP {}", i)))?;
+    unmaskers.iter().zip(server_responses.iter()).map(
+        |(unmasker, response)| unmask(unmasker, &response.xB)
+    ).collect()
+}
+
+// Removes the mask from the server's output and hashes it. The proof only covers the output up to small-order components, so it must be in the subgroup
+fn unmask(unmasker: &str, masked_output: &Point) -> std::result::Result<Vec<u8>, Error> {
+    if !masked_output.in_subgroup() {
+        return Err(Error::FailedToVerifyProof("VOPRF output is not in the subgroup".to_string()));
+    }
+    let unmasker_fl = Fl::from_str(unmasker).ok_or(Error::InvalidInput("unmasker must be a decimal integer less than the subgroup order".to_string()))?;
+        let unmasked = mul_scalar_ct(masked_output, &unmasker_fl);
         let (_, unmasked_bytes) = BigInt::from_str(&unmasked.x.to_dec_string())
             .map_err(|e|Error::InvalidInput(e.to_string()))?
             .to_bytes_be();
@@ -96,6 +131,15 @@ impl Client {
             .map(|o|o.into())
             .map_err(|e|e.into())
     }
+
+    /// `unmaskers` is an array of step1's unmaskers and `responses` an array of the server's responses, in the same order. Returns an array of the outputs
+    pub fn step2_batch(unmaskers: JsValue, responses: JsValue) -> Result<JsValue> {
+        let unmaskers: Vec<String> = serde_wasm_bindgen::from_value(unmaskers).map_err(|e|Error::InvalidInput(e.to_string()))?;
+        let res: Vec<DLEQProof> = serde_wasm_bindgen::from_value(responses).map_err(|e|Error::InvalidInput(e.to_string()))?;
+        step2_batch(unmaskers, res)
+            .map(|o|serde_wasm_bindgen::to_value(&o).unwrap())
+            .map_err(|e|e.into())
+    }
 }
 
 
@@ -108,14 +152,13 @@ mod tests {
     use babyjubjub_rs::{Point, B8, DLEQProof, Fl, FrBigIntConversion};
     use num_bigint::BigInt;
     use super::Result;
-    use crate::{step1, step2, Error};
+    use crate::{step1, step2, step2_batch, Error};
 
     // Simulates a server by multiplying the point by a private key
     // Note it does not perform security checks on the input before multiplying it by its private key
     // We trust that its response's xA is the correct public key
     fn mock_server(masked: &Point, privkey: Fl) -> std::result::Result<DLEQProof, Error> {
         DLEQProof::new(privkey, B8.clone(), masked.clone())
-        
         .map_err(|e|Error::InvalidInput(e.to_string()).into())
     }
 
@@ -123,7 +166,7 @@ mod tests {
         let masked_and_mask = step1(input)?;
         let privkey = &BigInt::from_str("69").unwrap();
         let proof = mock_server(&masked_and_mask.masked, Fl::from_bigint(&privkey))?;
-        step2(masked_and_mask.unmasker_keepthissecret, proof)
+        step2(masked_and_mask.unmasker_keepthissecret.to_string(), proof)
     }
 
     #[test]
@@ -132,5 +175,22 @@ mod tests {
         // Makes sure same input gives same output. This seems to be essentially all we need to test client-side unless i'm missing something.
         assert_eq!(mock_interaction("abc").unwrap(), mock_interaction("abc").unwrap());
     }
+
+    #[test]
+    fn test_batch() {
+        let inputs = ["abc", "def", "ghi"];
+        let step1s: Vec<_> = inputs.iter().map(|i| step1(i).unwrap()).collect();
+        let privkey = Fl::from_bigint(&BigInt::from_str("69").unwrap());
+        let server_responses = || step1s.iter().map(|s| mock_server(&s.masked, privkey.clone()).unwrap()).collect::<Vec<DLEQProof>>();
+        let unmaskers: Vec<String> = step1s.iter().map(|s| s.unmasker_keepthissecret.to_string()).collect();
+
+        let outputs = step2_batch(unmaskers.clone(), server_responses()).unwrap();
+        let expected: Vec<Vec<u8>> = inputs.iter().map(|i| mock_interaction(i).unwrap()).collect();
+        assert_eq!(outputs, expected);
+
+        let mut proofs = server_responses();
+        proofs[1].xB = proofs[0].xB.clone();
+        assert!(matches!(step2_batch(unmaskers, proofs), Err(Error::FailedToVerifyProof(_))));
+    }
     // TODO: test distribution of outputs is random
 }
\ No newline at end of file
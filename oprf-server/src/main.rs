@@ -1,9 +1,12 @@
 use std::env;
-use babyjubjub_rs::{Point, DLEQProof, B8, Fl, FrBigIntConversion};
+use babyjubjub_rs::{Point, DLEQProof, B8};
+use babyjubjub_elgamal::secret::SecretScalar;
+use babyjubjub_elgamal::encoding::CompactEncoding;
 use num_bigint::{BigInt};
 use ratelimit::{get_redis_client, rate_limit, RateLimit};
 use rocket::{State, serde::json::Json, response::status::BadRequest, time::Instant};
 use serde::{Serialize, Deserialize};
+use zeroize::Zeroizing;
 
 mod ratelimit;
 mod cors;
@@ -16,9 +19,9 @@ pub struct VOPRFOutput {
     pub proof: DLEQProof,
 }
 
+/// The private key is zeroized when the server shuts down and prints as REDACTED
 pub struct Keys {
-    pub privkey: BigInt,
-    pub priv_fl: Fl,
+    pub privkey: SecretScalar,
     pub pubkey: Point,
 }
 
@@ -36,46 +39,66 @@ fn get_pubkey(keys: &State<Keys>) -> Json<Point> {
     Json(keys.pubkey.clone())
 }
 
+/// The public key as the hex of its 32-byte compressed encoding
+#[get("/pub-compact")]
+fn get_pubkey_compact(keys: &State<Keys>) -> String {
+    hex::encode(keys.pubkey.compress())
+}
+
 /// This gives a `DLEQProof` that the OPRF was computed correctly. The output of the OPRF is the `DLEQProof`'s `xB`.
-#[post("/voprf", format = "json", data = "<point>")]
-fn index(keys: &State<Keys>, _r: RateLimit, point: Json<Point>) -> Result<Json<DLEQProof>, BadRequest<&'static str>> {
+/// With `?encoding=compact`, the body is a JSON string of the hex of the point's 32-byte compressed encoding, and the proof is returned as the hex of its compact encoding
+#[post("/voprf?<encoding>", format = "json", data = "<body>")]
+fn index(keys: &State<Keys>, _r: RateLimit, body: &str, encoding: Option<&str>) -> Result<String, BadRequest<String>> {
     // let now = Instant::now();
-    // Check it is safe to proceed, i.e. point is on the curve and in subgroup
-    if !point.on_curve() {
-        return Err(BadRequest(Some("Not on curve")));
-    }
+    let bad_request = |msg: String| BadRequest(Some(msg));
+    let point = match encoding {
+        None | Some("json") => {
+            let point: Point = serde_json::from_str(body).map_err(|e| bad_request(e.to_string()))?;
+            // Check it is safe to proceed, i.e. point is on the curve and in subgroup
+            if !point.on_curve() {
+                return Err(bad_request("Not on curve".to_string()));
+            }
+            // Note: in_subgroup just checks that order of the point is the order of the subgroup
+            if !point.in_subgroup() {
+                return Err(bad_request("Not in subgroup".to_string()));
+            }
+            point
+        },
+        Some("compact") => {
+            let hex_point: String = serde_json::from_str(body).map_err(|e| bad_request(e.to_string()))?;
+            let bytes = hex::decode(hex_point).map_err(|e| bad_request(format!("point is not hex: {}", e)))?;
+            // from_compact checks the point is on the curve and in the subgroup
+            Point::from_compact(&bytes).map_err(|e| bad_request(e.to_string()))?
+        },
+        Some(other) => return Err(bad_request(format!("unknown encoding \"{}\"", other))),
+    };
 
-    // Note: in_subgroup just checks that order of the point is the order of the subgroup
-    if !point.in_subgroup() {
-        return Err(BadRequest(Some("Not in subgroup")));
-    }
-
-    let proof = DLEQProof::new(keys.priv_fl.clone(), B8.clone(), point.into_inner())
-        .map_err(|e|BadRequest(Some("Error computing zk dleq proof")))?;
+    let proof = DLEQProof::new(*keys.privkey.expose_secret(), B8.clone(), point)
+        .map_err(|_| bad_request("Error computing zk dleq proof".to_string()))?;
     // println!("Time to compute proof: {}s\n", now.elapsed().as_seconds_f32());
-    Ok(Json(proof))
+    Ok(match encoding {
+        Some("compact") => hex::encode(proof.to_compact()),
+        _ => serde_json::to_string(&proof).unwrap(),
+    })
 }
 
 #[launch]
 fn rocket() -> _ {
     // Get the private key env var
-    let privkey: BigInt = env::var("OPRF_KEY")
-        .expect("OPRF_KEY must be an environment variable. It should be a decimal string representing a random integer between 0 and the order of the curve's subgroup.")
-        .parse::<BigInt>()
-        .unwrap();
-    let priv_fl = Fl::from_bigint(&privkey);
-    let pubkey = B8.mul_scalar(&privkey);
+    let privkey_str = Zeroizing::new(env::var("OPRF_KEY")
+        .expect("OPRF_KEY must be an environment variable. It should be a decimal string representing a random integer between 0 and the order of the curve's subgroup."));
+    let privkey = SecretScalar::from_decimal_str(&privkey_str).expect("OPRF_KEY must be a decimal integer");
+    let pubkey = privkey.mul_b8();
 
     let rlredis = get_redis_client().unwrap();
 
     rocket::build()
     .manage(Keys {
         privkey,
-        priv_fl,
         pubkey,
     })
     .manage(rlredis)
     .attach(cors::Cors)
-    .mount("/", routes![index, good_morn, get_pubkey, example_point_maker])
+    .mount("/", routes![index, good_morn, get_pubkey, get_pubkey_compact, example_point_maker])
     .register("/", catchers![rate_limit])
 }
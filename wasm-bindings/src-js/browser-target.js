@@ -1,5 +1,5 @@
 // This is gold https://stackoverflow.com/a/71673305/14039774
-import init, { enableErrors, auditorKeygen, auditorDecrypt, litKeygen, litDecrypt, msgToPoint, pointToMsg, litPubkeyShare, auditorPubkeyShare, sharedPubkey, node_from_seed, random_node, read_node, encryptPoint } from '../bindings/elgamal_babyjubjub';
+import init, { enableErrors, auditorKeygen, auditorDecrypt, litKeygen, litDecrypt, msgToPoint, pointToMsg, litPubkeyShare, auditorPubkeyShare, sharedPubkey, node_from_seed, random_node, read_node, encryptPointLabeled } from '../bindings/elgamal_babyjubjub';
 import wasmData from '../bindings/elgamal_babyjubjub_bg.wasm';
 const { randomBytes } = require("crypto");
 // console.log(Buffer.from(wasmData))
@@ -69,16 +69,15 @@ export class Encryption {
         this.toPubkey = sharedPubkey([litPubkey, auditorPubkey]);
     }
 
-    // msg is a string
-    async encrypt(msg) {
+    // msg is a string. label is a CiphertextLabel ({ user_id, purpose, expiry }) saying what the ciphertext may be decrypted for
+    async encrypt(msg, label) {
         await waitTilLoaded();
         const pt = msgToPoint(msg);
-        // Nonce should be 64 bytes for optimal randomness. However, it probably doesn't matter
-        const nonce = BigInt("0x"+randomBytes(64).toString("hex")).toString();
         return {
             message: msg,
             messageAsPoint: pt,
-            encrypted: encryptPoint(pt, await this.toPubkey, nonce)
+            // Lit and the auditor only decrypt the TDH2 ciphertext, `encrypted.ciphertext`
+            encrypted: encryptPointLabeled(pt, await this.toPubkey, label)
         }
     }
 }
@@ -5,12 +5,21 @@ use std::{str::FromStr};
 
 use wasm_bindgen::prelude::*;
 use num_bigint::BigInt;
-use babyjubjub_rs::{Point, ToDecimalString, ElGamalEncryption, encrypt_elgamal, PrivateKey};
-
-use babyjubjub_elgamal::{self, Node, KeygenHelper, decrypt, calculate_pubkey};
+use babyjubjub_rs::{Point, ToDecimalString, ElGamalEncryption, PrivateKey};
+
+use babyjubjub_elgamal::{self, Node, KeygenHelper, KeygenCommitment, RawDecryptionShare, RerandomizationProof, PublicKey, Ciphertext, DecryptionShare, VerifiableDecryptionShare, PubkeyShare, try_decrypt, try_calculate_pubkey, reshared_pubkey, verification_key};
+use babyjubjub_elgamal::hybrid::{self, HybridCiphertext};
+use babyjubjub_elgamal::tdh2::{encrypt_labeled, CiphertextLabel, LabeledCiphertext, Tdh2Ciphertext};
+use babyjubjub_elgamal::encryption_proof::{self, EncryptionProof};
+use babyjubjub_elgamal::exponential::{self, HomomorphicEncryption};
+use babyjubjub_elgamal::encoding::CompactEncoding;
+use babyjubjub_elgamal::keystore;
 extern crate console_error_panic_hook;
 use std::panic;
 
+// Errors from the babyjubjub-elgamal crate and from deserializing inputs are thrown as JS errors rather than panicking
+type Result<T> = std::result::Result<T, JsError>;
+
 // Note: no constant-time gaurantees
 
 
@@ -20,33 +29,204 @@ pub fn enableErrors() {
 }
 
 #[wasm_bindgen]
-pub fn msgToPoint(m: String) -> JsValue {
-    let m_big = BigInt::from_str(&m).unwrap();
+pub fn msgToPoint(m: String) -> Result<JsValue> {
+    let m_big = BigInt::from_str(&m)?;
     let p = Point::from_msg_vartime(&m_big);
-    serde_wasm_bindgen::to_value(&p).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&p)?)
     // serde_json::to_string(&p).unwrap()
 }
 
 #[wasm_bindgen]
-pub fn pointToMsg(point: JsValue) -> JsValue {
-    let p: Point = serde_wasm_bindgen::from_value(point).unwrap();
+pub fn pointToMsg(point: JsValue) -> Result<JsValue> {
+    let p: Point = serde_wasm_bindgen::from_value(point)?;
     let m = p.to_msg().to_dec_string();
-    serde_wasm_bindgen::to_value(&m).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&m)?)
     // Point::from_xy_strings(x, y).to_msg().to_dec_string()
 }
 
+// Compact binary encodings: 32 bytes per point, 64 per ciphertext, 40 per KeygenHelper, 36 per decryption share, 260 per verifiable decryption share
+// and 160 plus the label per labeled ciphertext.
+// The *FromBytes functions throw on anything that isn't a canonical encoding of a valid value
+#[wasm_bindgen]
+pub fn pointToBytes(point: JsValue) -> Result<Vec<u8>> {
+    let p: Point = serde_wasm_bindgen::from_value(point)?;
+    Ok(p.to_compact())
+}
+
+#[wasm_bindgen]
+pub fn pointFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&Point::from_compact(bytes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn encryptedToBytes(encrypted: JsValue) -> Result<Vec<u8>> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    Ok(e.to_compact())
+}
+
+#[wasm_bindgen]
+pub fn encryptedFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&Ciphertext::from_compact(bytes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn keygenHelperToBytes(helper: JsValue) -> Result<Vec<u8>> {
+    let h: KeygenHelper = serde_wasm_bindgen::from_value(helper)?;
+    Ok(h.to_compact())
+}
+
+#[wasm_bindgen]
+pub fn keygenHelperFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&KeygenHelper::from_compact(bytes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn decryptionShareToBytes(share: JsValue) -> Result<Vec<u8>> {
+    let s: DecryptionShare = serde_wasm_bindgen::from_value(share)?;
+    Ok(s.to_compact())
+}
+
+#[wasm_bindgen]
+pub fn decryptionShareFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&DecryptionShare::from_compact(bytes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn verifiableDecryptionShareToBytes(share: JsValue) -> Result<Vec<u8>> {
+    let s: VerifiableDecryptionShare = serde_wasm_bindgen::from_value(share)?;
+    Ok(s.to_compact())
+}
+
 #[wasm_bindgen]
-pub fn encryptPoint(msg: JsValue, pubkey: JsValue, nonce: String) -> JsValue {
-    let m: Point = serde_wasm_bindgen::from_value(msg).unwrap();
-    let p: Point = serde_wasm_bindgen::from_value(pubkey).unwrap();
+pub fn verifiableDecryptionShareFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&VerifiableDecryptionShare::from_compact(bytes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn labeledCiphertextToBytes(encrypted: JsValue) -> Result<Vec<u8>> {
+    let e: LabeledCiphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    Ok(e.to_compact())
+}
+
+#[wasm_bindgen]
+pub fn labeledCiphertextFromBytes(bytes: &[u8]) -> Result<JsValue> {
+    Ok(serde_wasm_bindgen::to_value(&LabeledCiphertext::from_compact(bytes)?)?)
+}
+
+// Throws if the public key isn't on the curve and in the subgroup
+#[wasm_bindgen]
+pub fn encryptPoint(msg: JsValue, pubkey: JsValue, nonce: String) -> Result<JsValue> {
+    let m: Point = serde_wasm_bindgen::from_value(msg)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
     // let msg = Point::from_xy_strings(msg_x, msg_y);
     // let pk = Point::from_xy_strings(pub_x, pub_y);
-    let nonce_big: BigInt = BigInt::from_str(&nonce).unwrap();
-    let e = encrypt_elgamal(&p, &nonce_big, &m);
-    serde_wasm_bindgen::to_value(&e).unwrap()
+    let nonce_big: BigInt = BigInt::from_str(&nonce)?;
+    let e = babyjubjub_elgamal::encrypt(&p, &nonce_big, &m);
+    Ok(serde_wasm_bindgen::to_value(&e)?)
     // serde_json::to_string(&e).unwrap()
 }
 
+// Encrypts the number `m` as m*B8, so that ciphertexts can be added with `addEncrypted`
+#[wasm_bindgen]
+pub fn encryptExponential(m: u64, pubkey: JsValue, nonce: String) -> Result<JsValue> {
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let nonce_big: BigInt = BigInt::from_str(&nonce)?;
+    Ok(serde_wasm_bindgen::to_value(&exponential::encrypt_exponential(&p, m, &nonce_big))?)
+}
+
+// Adds two `encryptExponential` ciphertexts, giving an encryption of the sum of their messages
+#[wasm_bindgen]
+pub fn addEncrypted(a: JsValue, b: JsValue) -> Result<JsValue> {
+    let a: ElGamalEncryption = serde_wasm_bindgen::from_value(a)?;
+    let b: ElGamalEncryption = serde_wasm_bindgen::from_value(b)?;
+    Ok(serde_wasm_bindgen::to_value(&a.add_ciphertext(&b))?)
+}
+
+// Same as `finalDecrypt` for `encryptExponential` ciphertexts. Returns the message if it's at most `max`
+#[wasm_bindgen]
+pub fn finalDecryptExponential(encryptedMsg: JsValue, decryptShares: JsValue, numSharesNeeded: usize, max: u64) -> Result<u64> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encryptedMsg)?;
+    let s: Vec<DecryptionShare> = serde_wasm_bindgen::from_value(decryptShares)?;
+    Ok(exponential::decrypt_exponential(e, s, numSharesNeeded as u64, max)?)
+}
+
+// Re-randomizes an encryption to `pubkey` so it can't be linked to the original. `nonce` must be secret and random
+#[wasm_bindgen]
+pub fn rerandomize(encrypted: JsValue, pubkey: JsValue, nonce: String) -> Result<JsValue> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let nonce_big: BigInt = BigInt::from_str(&nonce)?;
+    Ok(serde_wasm_bindgen::to_value(&babyjubjub_elgamal::rerandomize_with_nonce(&p, &e, &nonce_big))?)
+}
+
+// Re-randomizes an encryption and proves the result encrypts the same message. Returns `[rerandomized, proof]`
+#[wasm_bindgen]
+pub fn rerandomizeWithProof(encrypted: JsValue, pubkey: JsValue) -> Result<JsValue> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let result = babyjubjub_elgamal::rerandomize_with_proof(&p, &e)?;
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+#[wasm_bindgen]
+pub fn verifyRerandomization(proof: JsValue, pubkey: JsValue, original: JsValue, rerandomized: JsValue) -> Result<bool> {
+    let proof: RerandomizationProof = serde_wasm_bindgen::from_value(proof)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let o: Ciphertext = serde_wasm_bindgen::from_value(original)?;
+    let r: Ciphertext = serde_wasm_bindgen::from_value(rerandomized)?;
+    Ok(proof.verify(&p, &o, &r))
+}
+
+// Encrypts the same point to several public keys with one nonce. Returns `{ c1, c2s }`; `{ c1, c2: c2s[j] }` is the encryption for `pubkeys[j]`
+#[wasm_bindgen]
+pub fn encryptPointMulti(msg: JsValue, pubkeys: JsValue, nonce: String) -> Result<JsValue> {
+    let m: Point = serde_wasm_bindgen::from_value(msg)?;
+    let p: Vec<PublicKey> = serde_wasm_bindgen::from_value(pubkeys)?;
+    let nonce_big: BigInt = BigInt::from_str(&nonce)?;
+    let e = babyjubjub_elgamal::multi_recipient::encrypt_multi_with_nonce(&p, &nonce_big, &m);
+    Ok(serde_wasm_bindgen::to_value(&e)?)
+}
+
+// Same as `encryptPoint`, but also returns a commitment to the message and a proof the ciphertext encrypts the committed message: `{ encrypted, commitment, proof }`.
+// `blinding` must be random and kept secret
+#[wasm_bindgen]
+pub fn encryptPointWithProof(msg: JsValue, pubkey: JsValue, nonce: String, blinding: String) -> Result<JsValue> {
+    let m: Point = serde_wasm_bindgen::from_value(msg)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let nonce_big: BigInt = BigInt::from_str(&nonce)?;
+    let blinding_big: BigInt = BigInt::from_str(&blinding)?;
+    let proven = encryption_proof::encrypt_with_proof(&p, &m, &nonce_big, &blinding_big);
+    Ok(serde_wasm_bindgen::to_value(&proven)?)
+}
+
+#[wasm_bindgen]
+pub fn verifyEncryptionProof(proof: JsValue, pubkey: JsValue, encrypted: JsValue, commitment: JsValue) -> Result<bool> {
+    let proof: EncryptionProof = serde_wasm_bindgen::from_value(proof)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    let c: Point = serde_wasm_bindgen::from_value(commitment)?;
+    Ok(proof.verify(&p, &e, &c))
+}
+
+// Encrypts a point bound to a label `{ user_id, purpose, expiry }`. This is what the decryptor nodes expect to be sent. Its `ciphertext` field's c1 and c2 can be passed to `finalDecrypt`
+#[wasm_bindgen]
+pub fn encryptPointLabeled(msg: JsValue, pubkey: JsValue, label: JsValue) -> Result<JsValue> {
+    let m: Point = serde_wasm_bindgen::from_value(msg)?;
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let l: CiphertextLabel = serde_wasm_bindgen::from_value(label)?;
+    Ok(serde_wasm_bindgen::to_value(&encrypt_labeled(&p, &m, &l))?)
+}
+
+// Encrypts a byte message of any length, such as a whole document. The result has no proof of knowledge of the nonce, so the nodes can only
+// partially decrypt its c1 with `nodePartialDecryptC1`, and the shares are combined with `finalDecryptBytes`.
+// The nonce is always random, since it determines the symmetric key and reusing it would reuse the key with the same ChaCha20 nonce
+#[wasm_bindgen]
+pub fn encryptBytes(msg: &[u8], pubkey: JsValue) -> Result<JsValue> {
+    let p: PublicKey = serde_wasm_bindgen::from_value(pubkey)?;
+    let e = hybrid::encrypt_bytes(&p, msg);
+    Ok(serde_wasm_bindgen::to_value(&e)?)
+}
+
 // #[wasm_bindgen]
 // pub fn decryptShare(node: JsValue, msgPoint: JsValue) -> JsValue {
 //     let n: Node = serde_wasm_bindgen::from_value(node).unwrap();
@@ -56,126 +236,253 @@ pub fn encryptPoint(msg: JsValue, pubkey: JsValue, nonce: String) -> JsValue {
 // }
 
 #[wasm_bindgen]
-pub fn finalDecrypt(encryptedMsg: JsValue, decryptShares: JsValue, numSharesNeeded: usize) -> JsValue {
-    let e: ElGamalEncryption = serde_wasm_bindgen::from_value(encryptedMsg).unwrap();
-    let s: Vec<Point> = serde_wasm_bindgen::from_value(decryptShares).unwrap();
-    let d = babyjubjub_elgamal::decrypt(e, s, numSharesNeeded as u64);
-    serde_wasm_bindgen::to_value(&d).unwrap()
+pub fn finalDecrypt(encryptedMsg: JsValue, decryptShares: JsValue, numSharesNeeded: usize) -> Result<JsValue> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encryptedMsg)?;
+    let s: Vec<DecryptionShare> = serde_wasm_bindgen::from_value(decryptShares)?;
+    let d = try_decrypt(e, s, numSharesNeeded as u64)?;
+    Ok(serde_wasm_bindgen::to_value(&d)?)
 }
 
+// Decrypts using raw decryption shares (`{ node_idx, share }`) from any `thresholdNodes` nodes. The Lagrange bases are applied here rather than by the nodes
+#[wasm_bindgen]
+pub fn finalDecryptRaw(encryptedMsg: JsValue, rawDecryptShares: JsValue, thresholdNodes: usize) -> Result<JsValue> {
+    let e: Ciphertext = serde_wasm_bindgen::from_value(encryptedMsg)?;
+    let s: Vec<RawDecryptionShare> = serde_wasm_bindgen::from_value(rawDecryptShares)?;
+    let d = babyjubjub_elgamal::try_decrypt_raw(e, &s, thresholdNodes)?;
+    Ok(serde_wasm_bindgen::to_value(&d)?)
+}
 
-// These two functions can be deleted; they're just for some experimationt
 #[wasm_bindgen]
-pub fn random_node() -> JsValue {
+pub fn finalDecryptBytes(encryptedMsg: JsValue, decryptShares: JsValue, numSharesNeeded: usize) -> Result<Vec<u8>> {
+    let e: HybridCiphertext = serde_wasm_bindgen::from_value(encryptedMsg)?;
+    let s: Vec<DecryptionShare> = serde_wasm_bindgen::from_value(decryptShares)?;
+    Ok(hybrid::decrypt_bytes(&e, s, numSharesNeeded as u64)?)
+}
+
+#[wasm_bindgen]
+pub fn finalDecryptBytesRaw(encryptedMsg: JsValue, rawDecryptShares: JsValue, thresholdNodes: usize) -> Result<Vec<u8>> {
+    let e: HybridCiphertext = serde_wasm_bindgen::from_value(encryptedMsg)?;
+    let s: Vec<RawDecryptionShare> = serde_wasm_bindgen::from_value(rawDecryptShares)?;
+    Ok(hybrid::decrypt_bytes_raw(&e, &s, thresholdNodes)?)
+}
+
+// These functions can be deleted; they're just for some experimationt. They return the node exposed, with its keygen polynomial and keyshare in the clear
+#[wasm_bindgen]
+pub fn random_node() -> Result<JsValue> {
     // return some random node to see how it looks in JS
-    let n = Node::init_rnd(1, 3, 5);
-    serde_wasm_bindgen::to_value(&n).unwrap()
+    let n = Node::try_init_rnd(1, 3, 5)?;
+    Ok(serde_wasm_bindgen::to_value(&n.exposed())?)
 }
 
 #[wasm_bindgen]
-pub fn node_from_seed(seed: &[u8]) -> JsValue {
+pub fn node_from_seed(seed: &[u8]) -> Result<JsValue> {
     // return some random node to see how it looks in JS
     let as_vec = seed.to_vec();
-    let n = Node::init_from_seed(&as_vec, 1, 3, 5);
-    serde_wasm_bindgen::to_value(&n).unwrap()
+    let n = Node::try_init_from_seed(&as_vec, 1, 3, 5)?;
+    Ok(serde_wasm_bindgen::to_value(&n.exposed())?)
+}
+
+#[wasm_bindgen]
+pub fn read_node(node: JsValue) -> Result<JsValue> {
+    let n: Node = serde_wasm_bindgen::from_value(node)?;
+    Ok(serde_wasm_bindgen::to_value(&n.exposed())?)
 }
 
+// Encrypts a node with a password, giving a keystore JSON string that's safe to store. Nodes should be stored this way rather than as the objects `read_node` returns
 #[wasm_bindgen]
-pub fn read_node(node: JsValue) -> JsValue {
-    let n: Node = serde_wasm_bindgen::from_value(node).unwrap();
-    serde_wasm_bindgen::to_value(&n).unwrap()
+pub fn encryptNode(node: JsValue, password: &[u8]) -> Result<String> {
+    let n: Node = serde_wasm_bindgen::from_value(node)?;
+    let keystore = keystore::encrypt_node(&n, password)?;
+    Ok(serde_json::to_string(&keystore)?)
+}
+
+#[wasm_bindgen]
+pub fn decryptNode(keystore: &str, password: &[u8]) -> Result<JsValue> {
+    let k: keystore::EncryptedNode = serde_json::from_str(keystore)?;
+    Ok(serde_wasm_bindgen::to_value(&k.decrypt(password)?.exposed())?)
 }
 
 // Gets the keygen result from a seed. This returns the keygen polynomial's evaluation at 2. This should be given to the auditor, at node 2
 // * and should not be shared with anyone else *
 #[wasm_bindgen]
-pub fn litKeygen(seed: &[u8]) -> JsValue {
+pub fn litKeygen(seed: &[u8]) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let n = Node::init_from_seed(&as_vec, 1, 2, 2);
+    let n = Node::try_init_from_seed(&as_vec, 1, 2, 2)?;
     let keygen_evals_for_nodes = n.keygen_step1(2);
-    serde_wasm_bindgen::to_value(&keygen_evals_for_nodes[1]).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&keygen_evals_for_nodes[1])?)
 }
 
 // Gets the keygen result from a seed. This returns the keygen polynomial's evaluation at 1 must be given to the lit protocol, at node 1
 // * and should not be shared with anyone else *
 #[wasm_bindgen]
-pub fn auditorKeygen(seed: &[u8]) -> JsValue {
+pub fn auditorKeygen(seed: &[u8]) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let n = Node::init_from_seed(&as_vec, 2, 2, 2);
+    let n = Node::try_init_from_seed(&as_vec, 2, 2, 2)?;
     let keygen_evals_for_nodes = n.keygen_step1(2);
-    serde_wasm_bindgen::to_value(&keygen_evals_for_nodes[0]).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&keygen_evals_for_nodes[0])?)
+}
+
+// Gets the Feldman commitment to Lit's keygen polynomial. This is public and must be given to the auditor so it can check `litKeygen`'s result
+#[wasm_bindgen]
+pub fn litKeygenCommitment(seed: &[u8]) -> Result<JsValue> {
+    let as_vec = seed.to_vec();
+    let n = Node::try_init_from_seed(&as_vec, 1, 2, 2)?;
+    Ok(serde_wasm_bindgen::to_value(&n.keygen_commitment())?)
+}
+
+// Gets the Feldman commitment to the auditor's keygen polynomial. This is public and must be given to Lit so it can check `auditorKeygen`'s result
+#[wasm_bindgen]
+pub fn auditorKeygenCommitment(seed: &[u8]) -> Result<JsValue> {
+    let as_vec = seed.to_vec();
+    let n = Node::try_init_from_seed(&as_vec, 2, 2, 2)?;
+    Ok(serde_wasm_bindgen::to_value(&n.keygen_commitment())?)
+}
+
+// Sets a 2-of-2 node's keyshare from its own keygen result and the other party's, checking the other party's result against its commitment
+fn set_keyshare_2of2(n: &mut Node, other_keygen_eval: JsValue, other_keygen_commitment: JsValue) -> Result<()> {
+    let mut k: KeygenHelper = serde_wasm_bindgen::from_value(other_keygen_eval)?;
+    let c: KeygenCommitment = serde_wasm_bindgen::from_value(other_keygen_commitment)?;
+    if k.for_node != 1 && k.for_node != 2 {
+        return Err(JsError::new("keygen result must be for node 1 or node 2"));
+    }
+    // results stored before they recorded their sender can only have come from the other party
+    if k.from_node == 0 {
+        k.from_node = 3 - k.for_node;
+    }
+
+    let my_keygen_result = n.keygen_step1(2).remove(k.for_node - 1);
+    let my_commitment = n.keygen_commitment();
+    n.set_keyshare(&vec![&my_keygen_result, &k], &vec![&my_commitment, &c])?;
+    Ok(())
 }
 
 
 // This is what the Lit Protocol PKP doeswhenever called : 
 // 1. instantiates a node based on some deterministic but secret seed Lit protocol will provide
 // 2. sets the keygen polynomial based on this party's the other party's keygen result
-// 3. partially decrypts a msg
+// 3. partially decrypts a TDH2 ciphertext, refusing if its proof doesn't verify
 #[wasm_bindgen]
-pub fn litDecrypt(seed: &[u8], auditorKeygenEvalAt1: JsValue, encryptedC1: JsValue) -> JsValue {
+pub fn litDecrypt(seed: &[u8], auditorKeygenEvalAt1: JsValue, auditorKeygenCommitment: JsValue, encrypted: JsValue) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let mut n = Node::init_from_seed(&as_vec, 1, 2, 2);
-    let k: KeygenHelper = serde_wasm_bindgen::from_value(auditorKeygenEvalAt1).unwrap();
-    let e: Point = serde_wasm_bindgen::from_value(encryptedC1).unwrap();
+    let mut n = Node::try_init_from_seed(&as_vec, 1, 2, 2)?;
+    let e: Tdh2Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
 
-    let my_keygen_result = &n.keygen_step1(2)[0];
-    n.set_keyshare(&vec![&my_keygen_result,&k]);
+    set_keyshare_2of2(&mut n, auditorKeygenEvalAt1, auditorKeygenCommitment)?;
 
-    let result = n.partial_decrypt(&e);
-    serde_wasm_bindgen::to_value(&result).unwrap()
+    let result = n.try_partial_decrypt(&e, &vec![1, 2])?;
+    Ok(serde_wasm_bindgen::to_value(&result)?)
 }
 
 // This is what the Auditor doeswhenever called : 
 // 1. instantiates a node based on some secret key seed
 // 2. sets the keygen polynomial based on this party and the other party's keygen result
-// 3. fully decrypts a message
+// 3. fully decrypts a TDH2 ciphertext, refusing if its proof doesn't verify
 #[wasm_bindgen]
-pub fn auditorDecrypt(seed: &[u8], litKeygenEvalAt2: JsValue, encrypted: JsValue, litPartialDecryption: JsValue) -> JsValue {
+pub fn auditorDecrypt(seed: &[u8], litKeygenEvalAt2: JsValue, litKeygenCommitment: JsValue, encrypted: JsValue, litPartialDecryption: JsValue) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let mut n = Node::init_from_seed(&as_vec, 2, 2, 2);
-    let k: KeygenHelper = serde_wasm_bindgen::from_value(litKeygenEvalAt2).unwrap();
-    let e: ElGamalEncryption = serde_wasm_bindgen::from_value(encrypted).unwrap();
-    let d1: Point = serde_wasm_bindgen::from_value(litPartialDecryption).unwrap();
+    let mut n = Node::try_init_from_seed(&as_vec, 2, 2, 2)?;
+    let e: Tdh2Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    let d1: DecryptionShare = serde_wasm_bindgen::from_value(litPartialDecryption)?;
 
-    let my_keygen_result = &n.keygen_step1(2)[1];
-    n.set_keyshare(&vec![&my_keygen_result,&k]);
+    set_keyshare_2of2(&mut n, litKeygenEvalAt2, litKeygenCommitment)?;
 
-    let d2 = n.partial_decrypt(&e.c1);
+    let d2 = n.try_partial_decrypt(&e, &vec![1, 2])?;
 
-    let decrypted = decrypt(e, vec![d1,d2], 2);
-    serde_wasm_bindgen::to_value(&decrypted).unwrap()
+    let decrypted = try_decrypt(Ciphertext::try_from(e.elgamal())?, vec![d1,d2], 2)?;
+    Ok(serde_wasm_bindgen::to_value(&decrypted)?)
 }
 
 #[wasm_bindgen]
-pub fn auditorPubkeyShare(seed: &[u8], litKeygenEvalAt2: JsValue) -> JsValue {
+pub fn auditorPubkeyShare(seed: &[u8], litKeygenEvalAt2: JsValue, litKeygenCommitment: JsValue) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let mut n = Node::init_from_seed(&as_vec, 2, 2, 2);
-    let k: KeygenHelper = serde_wasm_bindgen::from_value(litKeygenEvalAt2).unwrap();
+    let mut n = Node::try_init_from_seed(&as_vec, 2, 2, 2)?;
 
-    let my_keygen_result = &n.keygen_step1(2)[1];
-    n.set_keyshare(&vec![&my_keygen_result,&k]);
+    set_keyshare_2of2(&mut n, litKeygenEvalAt2, litKeygenCommitment)?;
 
     let pks = n.pubkey_share();
-    serde_wasm_bindgen::to_value(&pks).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&pks)?)
 }
 #[wasm_bindgen]
-pub fn litPubkeyShare(seed: &[u8], auditorKeygenEvalAt1: JsValue) -> JsValue {
+pub fn litPubkeyShare(seed: &[u8], auditorKeygenEvalAt1: JsValue, auditorKeygenCommitment: JsValue) -> Result<JsValue> {
     let as_vec = seed.to_vec();
-    let mut n = Node::init_from_seed(&as_vec, 1, 2, 2);
-    let k: KeygenHelper = serde_wasm_bindgen::from_value(auditorKeygenEvalAt1).unwrap();
+    let mut n = Node::try_init_from_seed(&as_vec, 1, 2, 2)?;
     
-    let my_keygen_result = &n.keygen_step1(2)[0];
-    n.set_keyshare(&vec![&my_keygen_result,&k]);
+    set_keyshare_2of2(&mut n, auditorKeygenEvalAt1, auditorKeygenCommitment)?;
 
     let pubkey = n.pubkey_share();
-    serde_wasm_bindgen::to_value(&pubkey).unwrap()
+    Ok(serde_wasm_bindgen::to_value(&pubkey)?)
+}
+
+// Resharing the 2-of-2 Lit/auditor key to a new committee, e.g. 3-of-5, without changing the public key:
+// 1. Lit and the auditor each call `litReshare`/`auditorReshare`, which return `[helpers, commitment]`. `helpers[j-1]` is secret and goes only to new node j,
+//    and `commitment` is public and goes to every new node
+// 2. New node j calls `nodeFromLitAuditorReshare` with the two helpers for it and both commitments, checking them against Lit's and the auditor's keygen commitments.
+//    The node it returns should be stored with `encryptNode`
+// 3. The new nodes give decryption shares with `nodePartialDecrypt`, which are combined with `finalDecrypt` as usual
+// `litAuditorResharedPubkey` gives the new committee's public key from the resharing commitments, which should be the same as `sharedPubkey`'s
+#[wasm_bindgen]
+pub fn litReshare(seed: &[u8], auditorKeygenEvalAt1: JsValue, auditorKeygenCommitment: JsValue, newThresholdNodes: usize, newTotalNodes: usize) -> Result<JsValue> {
+    let as_vec = seed.to_vec();
+    let mut n = Node::try_init_from_seed(&as_vec, 1, 2, 2)?;
+    set_keyshare_2of2(&mut n, auditorKeygenEvalAt1, auditorKeygenCommitment)?;
+    Ok(serde_wasm_bindgen::to_value(&n.reshare_step1(newThresholdNodes, newTotalNodes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn auditorReshare(seed: &[u8], litKeygenEvalAt2: JsValue, litKeygenCommitment: JsValue, newThresholdNodes: usize, newTotalNodes: usize) -> Result<JsValue> {
+    let as_vec = seed.to_vec();
+    let mut n = Node::try_init_from_seed(&as_vec, 2, 2, 2)?;
+    set_keyshare_2of2(&mut n, litKeygenEvalAt2, litKeygenCommitment)?;
+    Ok(serde_wasm_bindgen::to_value(&n.reshare_step1(newThresholdNodes, newTotalNodes)?)?)
+}
+
+#[wasm_bindgen]
+pub fn nodeFromLitAuditorReshare(idx: usize, thresholdNodes: usize, totalNodes: usize, reshareEvalsForMe: JsValue, reshareCommitments: JsValue, litKeygenCommitment: JsValue, auditorKeygenCommitment: JsValue) -> Result<JsValue> {
+    let evals: Vec<KeygenHelper> = serde_wasm_bindgen::from_value(reshareEvalsForMe)?;
+    let commitments: Vec<KeygenCommitment> = serde_wasm_bindgen::from_value(reshareCommitments)?;
+    let keygen_commitments: Vec<KeygenCommitment> = vec![
+        serde_wasm_bindgen::from_value(litKeygenCommitment)?,
+        serde_wasm_bindgen::from_value(auditorKeygenCommitment)?,
+    ];
+    // Lit is node 1 and the auditor is node 2 of the old committee
+    let old_verification_keys: Vec<Point> = (1..3).map(|i| verification_key(&keygen_commitments, i)).collect();
+    let n = Node::init_from_reshare(
+        idx, thresholdNodes, totalNodes, 2, &vec![1, 2],
+        &evals.iter().collect(), &commitments.iter().collect(), &old_verification_keys
+    )?;
+    // exposed, so nodePartialDecrypt can take it back
+    Ok(serde_wasm_bindgen::to_value(&n.exposed())?)
+}
+
+#[wasm_bindgen]
+pub fn litAuditorResharedPubkey(reshareCommitments: JsValue) -> Result<JsValue> {
+    let commitments: Vec<KeygenCommitment> = serde_wasm_bindgen::from_value(reshareCommitments)?;
+    let result = reshared_pubkey(&commitments.iter().collect(), &vec![1, 2], 2)?;
+    Ok(serde_wasm_bindgen::to_value(&result)?)
+}
+
+// Partially decrypts a TDH2 ciphertext with a node, such as one from `nodeFromLitAuditorReshare`, refusing if the ciphertext's proof doesn't verify
+#[wasm_bindgen]
+pub fn nodePartialDecrypt(node: JsValue, encrypted: JsValue, nodesToDecryptFrom: Vec<u32>) -> Result<JsValue> {
+    let n: Node = serde_wasm_bindgen::from_value(node)?;
+    let e: Tdh2Ciphertext = serde_wasm_bindgen::from_value(encrypted)?;
+    Ok(serde_wasm_bindgen::to_value(&n.try_partial_decrypt(&e, &nodesToDecryptFrom)?)?)
+}
+
+// Partially decrypts the c1 of a plain, hybrid, exponential, multi-recipient or rerandomized ciphertext with a node. There's no proof to check,
+// so a c1 chosen by an attacker can leak the message of any ciphertext sharing it. Only use it for ciphertexts you trust, and `nodePartialDecrypt` otherwise
+#[wasm_bindgen]
+pub fn nodePartialDecryptC1(node: JsValue, c1: JsValue, nodesToDecryptFrom: Vec<u32>) -> Result<JsValue> {
+    let n: Node = serde_wasm_bindgen::from_value(node)?;
+    let c: Point = serde_wasm_bindgen::from_value(c1)?;
+    Ok(serde_wasm_bindgen::to_value(&n.try_partial_decrypt_c1(&c, &nodesToDecryptFrom)?)?)
 }
 
 #[wasm_bindgen]
-pub fn sharedPubkey(pubkeyShares: JsValue) -> JsValue {
-    let s: Vec<Point> = serde_wasm_bindgen::from_value(pubkeyShares).unwrap();
-    let result = calculate_pubkey(s).unwrap();
-    serde_wasm_bindgen::to_value(&result).unwrap()
+pub fn sharedPubkey(pubkeyShares: JsValue) -> Result<JsValue> {
+    let s: Vec<PubkeyShare> = serde_wasm_bindgen::from_value(pubkeyShares)?;
+    let result = try_calculate_pubkey(s)?;
+    Ok(serde_wasm_bindgen::to_value(&result)?)
 }
 
 /* For standard, nonthreshold ElGamal: */
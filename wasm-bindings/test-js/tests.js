@@ -13,9 +13,10 @@ describe("Encryption", function() {
     });
     it("encrypt -> decrypt", async function() {
         const msg = 12345678901234567890n
-        const encrypted = await this.encryption.encrypt(msg.toString());
-        const litPartialDecryption = await this.lit.partialDecrypt(this.auditorKeygenForLit, encrypted.encrypted.c1);
-        const fullDecryption = await this.auditor.decrypt(this.litKeygenForAuditor, encrypted.encrypted, litPartialDecryption);
+        const label = { user_id: "user 1", purpose: "court order", expiry: 2000000000 };
+        const encrypted = await this.encryption.encrypt(msg.toString(), label);
+        const litPartialDecryption = await this.lit.partialDecrypt(this.auditorKeygenForLit, encrypted.encrypted.ciphertext);
+        const fullDecryption = await this.auditor.decrypt(this.litKeygenForAuditor, encrypted.encrypted.ciphertext, litPartialDecryption);
         expect(msg.toString()).to.equal(fullDecryption);
     })
 });
\ No newline at end of file